@@ -0,0 +1,309 @@
+use std::time::Duration;
+
+use bevy::{
+    core::FrameCount,
+    prelude::*,
+    time::common_conditions::on_real_timer,
+    utils::{get_short_name, HashMap},
+};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+use lightyear::prelude::{
+    server::{ConnectionManager, ReplicationTarget},
+    ClientId, Replicating,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{typed::TypedDebugModule, viewing::DebugModuleSystems};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// Entities listed for each client, the ones using the most bandwidth
+const MAX_ENTITIES: usize = 20;
+
+/// Replication traffic of the server by component and by entity, for each client. Only the
+/// components registered with [`ReplicationBandwidth::with_component`] are measured.
+///
+/// Lightyear doesn't report what it sends per component, so the traffic is only estimated: every
+/// frame a replicated component changes counts as an update the size of the component encoded
+/// with bincode. Lightyear's own encoding, send interval, batching and interest management are
+/// not taken into account, so this tells which components and entities weigh the most rather
+/// than what goes through the network.
+#[derive(Clone, Default)]
+pub struct ReplicationBandwidth {
+    components: Vec<fn(&mut App, DebugModuleSystems)>,
+}
+
+impl ReplicationBandwidth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_component<C: Component + Serialize>(mut self) -> Self {
+        self.components.push(|app, set| {
+            app.add_systems(
+                Last,
+                measure_component::<C>.before(sample_bandwidth).in_set(set),
+            );
+        });
+        self
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ReplicationBandwidthData {
+    pub clients: Vec<ClientBandwidth>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientBandwidth {
+    pub client_id: ClientId,
+    pub components: Vec<BandwidthRow>,
+    /// The [`MAX_ENTITIES`] entities using the most bandwidth
+    pub entities: Vec<BandwidthRow>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BandwidthRow {
+    /// Component name or entity
+    pub name: String,
+    pub bytes_per_second: f32,
+    pub updates_per_second: f32,
+}
+
+/// Traffic counted since the last sample
+#[derive(Resource, Default)]
+struct BandwidthWindow {
+    /// Real time the counting started at, `None` until the first measurement
+    started: Option<Duration>,
+    components: HashMap<(ClientId, &'static str), Traffic>,
+    entities: HashMap<(ClientId, Entity), Traffic>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Traffic {
+    bytes: u64,
+    updates: u32,
+}
+
+impl Traffic {
+    fn add(&mut self, bytes: u64) {
+        self.bytes += bytes;
+        self.updates += 1;
+    }
+
+    fn row(&self, name: String, elapsed: Duration) -> BandwidthRow {
+        let seconds = elapsed.as_secs_f32();
+        BandwidthRow {
+            name,
+            bytes_per_second: self.bytes as f32 / seconds,
+            updates_per_second: self.updates as f32 / seconds,
+        }
+    }
+}
+
+impl TypedDebugModule for ReplicationBandwidth {
+    type Data = ReplicationBandwidthData;
+
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems) {
+        app.init_resource::<BandwidthWindow>();
+        for add_component in &self.components {
+            add_component(app, set);
+        }
+        app.add_systems(
+            Last,
+            sample_bandwidth
+                .run_if(on_real_timer(SAMPLE_INTERVAL))
+                .in_set(set),
+        );
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: &ReplicationBandwidthData) {
+        ui.weak("Estimated from the changed components, not measured on the network");
+        if data.clients.is_empty() {
+            ui.weak("No clients connected");
+        }
+        for client in &data.clients {
+            let total: f32 = client
+                .components
+                .iter()
+                .map(|row| row.bytes_per_second)
+                .sum();
+            egui::CollapsingHeader::new(format!("{:?}: ~{:.0} B/s", client.client_id, total))
+                .id_source(("replication_bandwidth", client.client_id))
+                .default_open(true)
+                .show(ui, |ui| {
+                    ui.strong("Components");
+                    bandwidth_table(ui, ("components", client.client_id), &client.components);
+                    ui.strong("Largest entities");
+                    bandwidth_table(ui, ("entities", client.client_id), &client.entities);
+                });
+        }
+    }
+}
+
+#[cfg(feature = "enabled")]
+#[derive(Clone, Copy, PartialEq, Default)]
+enum SortColumn {
+    Name,
+    #[default]
+    Bytes,
+    Updates,
+}
+
+#[cfg(feature = "enabled")]
+/// Table of `rows`, sorted by the column whose header was last clicked. Clicking it again
+/// reverses the order.
+fn bandwidth_table(ui: &mut egui::Ui, id: impl std::hash::Hash, rows: &[BandwidthRow]) {
+    let id = ui.make_persistent_id(id);
+    let (mut column, mut descending) =
+        ui.data_mut(|data| *data.get_temp_mut_or_insert_with(id, || (SortColumn::default(), true)));
+
+    let mut sorted: Vec<_> = rows.iter().collect();
+    match column {
+        SortColumn::Name => sorted.sort_by(|a, b| a.name.cmp(&b.name)),
+        SortColumn::Bytes => {
+            sorted.sort_by(|a, b| a.bytes_per_second.total_cmp(&b.bytes_per_second))
+        }
+        SortColumn::Updates => {
+            sorted.sort_by(|a, b| a.updates_per_second.total_cmp(&b.updates_per_second))
+        }
+    }
+    if descending {
+        sorted.reverse();
+    }
+
+    egui::Grid::new(id).striped(true).show(ui, |ui| {
+        for (header, header_column) in [
+            ("Name", SortColumn::Name),
+            ("Estimated bytes/s", SortColumn::Bytes),
+            ("Updates/s", SortColumn::Updates),
+        ] {
+            let label = match (column == header_column, descending) {
+                (true, true) => format!("{header} ⏷"),
+                (true, false) => format!("{header} ⏶"),
+                (false, _) => header.to_string(),
+            };
+            if ui.button(label).clicked() {
+                descending = column != header_column || !descending;
+                column = header_column;
+            }
+        }
+        ui.end_row();
+
+        for row in sorted {
+            ui.label(&row.name);
+            ui.label(format!("{:.0}", row.bytes_per_second));
+            ui.label(format!("{:.1}", row.updates_per_second));
+            ui.end_row();
+        }
+    });
+
+    ui.data_mut(|data| data.insert_temp(id, (column, descending)));
+}
+
+fn measure_component<C: Component + Serialize>(
+    changed: Query<(Entity, Ref<C>, Option<&ReplicationTarget>), With<Replicating>>,
+    connection_manager: Res<ConnectionManager>,
+    frame: Res<FrameCount>,
+    time: Res<Time<Real>>,
+    mut last_frame: Local<Option<u32>>,
+    mut window: ResMut<BandwidthWindow>,
+) {
+    // After the module wasn't running, every component changed since its last run looks
+    // changed, so counting starts over on the next frame
+    let previous = last_frame.replace(frame.0);
+    if previous != Some(frame.0.wrapping_sub(1)) {
+        *window = BandwidthWindow {
+            started: Some(time.elapsed()),
+            ..default()
+        };
+        return;
+    }
+
+    let clients: Vec<_> = connection_manager.connected_clients().collect();
+    let name = std::any::type_name::<C>();
+
+    for (entity, component, target) in &changed {
+        if !component.is_changed() {
+            continue;
+        }
+        let bytes = bincode::serialized_size(&*component).unwrap_or(0);
+        for &client_id in &clients {
+            if target.is_some_and(|target| !target.target.targets(&client_id)) {
+                continue;
+            }
+            window
+                .components
+                .entry((client_id, name))
+                .or_default()
+                .add(bytes);
+            window
+                .entities
+                .entry((client_id, entity))
+                .or_default()
+                .add(bytes);
+        }
+    }
+}
+
+fn sample_bandwidth(
+    connection_manager: Res<ConnectionManager>,
+    time: Res<Time<Real>>,
+    mut window: ResMut<BandwidthWindow>,
+    mut data: ResMut<ReplicationBandwidthData>,
+) {
+    let now = time.elapsed();
+    let window = std::mem::replace(
+        &mut *window,
+        BandwidthWindow {
+            started: Some(now),
+            ..default()
+        },
+    );
+    let Some(started) = window.started else {
+        return;
+    };
+    let elapsed = now.saturating_sub(started);
+    if elapsed.is_zero() {
+        return;
+    }
+
+    let mut clients: Vec<_> = connection_manager
+        .connected_clients()
+        .map(|client_id| ClientBandwidth {
+            client_id,
+            components: vec![],
+            entities: vec![],
+        })
+        .collect();
+
+    for ((client_id, name), traffic) in window.components {
+        if let Some(client) = clients
+            .iter_mut()
+            .find(|client| client.client_id == client_id)
+        {
+            client
+                .components
+                .push(traffic.row(get_short_name(name), elapsed));
+        }
+    }
+    for ((client_id, entity), traffic) in window.entities {
+        if let Some(client) = clients
+            .iter_mut()
+            .find(|client| client.client_id == client_id)
+        {
+            client
+                .entities
+                .push(traffic.row(format!("{entity:?}"), elapsed));
+        }
+    }
+    for client in &mut clients {
+        client
+            .entities
+            .sort_by(|a, b| b.bytes_per_second.total_cmp(&a.bytes_per_second));
+        client.entities.truncate(MAX_ENTITIES);
+    }
+
+    data.clients = clients;
+}
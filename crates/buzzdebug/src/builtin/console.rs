@@ -0,0 +1,483 @@
+use std::str::FromStr;
+
+use bevy::prelude::*;
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+use lightyear::prelude::client;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::{register_debug_command, DebugCommand, DebugCommands},
+    recording::{load_ron, save_ron},
+    typed::TypedDebugModule,
+    viewing::DebugModuleSystems,
+};
+
+/// Lines kept in the console output
+const MAX_LINES: usize = 200;
+/// Entered lines kept in the console history
+const MAX_HISTORY: usize = 100;
+
+/// A command typed in the [`Console`], e.g. `spawn enemy 5`
+pub trait ConsoleCommand: Sized + Send + Sync + 'static {
+    /// First word of the command line, e.g. `spawn`
+    const NAME: &'static str;
+    /// Shown by `help`, e.g. `spawn <kind> <count>`
+    const USAGE: &'static str;
+
+    /// `args` are the words after the name
+    fn parse(args: &[&str]) -> Result<Self, String>;
+
+    /// Possible values of the last argument being typed, the previous ones given in `args`
+    fn complete(_args: &[&str]) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// Argument `index` of a command, parsed as `T`. `name` is used in the error message.
+pub fn parse_arg<T: FromStr>(args: &[&str], index: usize, name: &str) -> Result<T, String> {
+    let arg = args
+        .get(index)
+        .ok_or_else(|| format!("missing argument <{name}>"))?;
+    arg.parse().map_err(|_| format!("invalid <{name}>: {arg}"))
+}
+
+/// A client-local command typed in the console
+#[derive(Event, Clone, Debug)]
+pub struct LocalConsoleCommand<C> {
+    pub command: C,
+}
+
+/// Command waiting to be run by the console's system
+type PendingCommand = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+// Without the UI, only `register` is used, to keep the protocol of the commands
+#[cfg_attr(not(feature = "enabled"), allow(dead_code))]
+#[derive(Clone)]
+struct RegisteredCommand {
+    name: &'static str,
+    usage: &'static str,
+    server_side: bool,
+    register: fn(&mut App),
+    run: fn(&[&str]) -> Result<PendingCommand, String>,
+    complete: fn(&[&str]) -> Vec<String>,
+}
+
+/// Console where typed commands are run, either on the client (read them as
+/// [`LocalConsoleCommand`] events) or on the server (read them as
+/// [`DebugCommandEvent`](crate::commands::DebugCommandEvent)s)
+#[derive(Clone, Default)]
+pub struct Console {
+    commands: Vec<RegisteredCommand>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_local_command<C: ConsoleCommand>(mut self) -> Self {
+        self.commands.push(RegisteredCommand {
+            name: C::NAME,
+            usage: C::USAGE,
+            server_side: false,
+            register: |app| {
+                app.add_event::<LocalConsoleCommand<C>>();
+            },
+            run: |args| {
+                let command = C::parse(args)?;
+                Ok(Box::new(move |world: &mut World| {
+                    world.send_event(LocalConsoleCommand { command });
+                }))
+            },
+            complete: C::complete,
+        });
+        self
+    }
+
+    pub fn with_server_command<C: ConsoleCommand + DebugCommand>(mut self) -> Self {
+        self.commands.push(RegisteredCommand {
+            name: C::NAME,
+            usage: C::USAGE,
+            server_side: true,
+            register: register_debug_command::<C>,
+            run: |args| {
+                let mut commands = DebugCommands::default();
+                commands.send(C::parse(args)?);
+                Ok(Box::new(move |world: &mut World| {
+                    let Some(mut connection) =
+                        world.get_resource_mut::<client::ConnectionManager>()
+                    else {
+                        error!("Cannot send {} without a connection", C::NAME);
+                        return;
+                    };
+                    commands.apply(&mut connection);
+                }))
+            },
+            complete: C::complete,
+        });
+        self
+    }
+
+    #[cfg(feature = "enabled")]
+    fn command(&self, name: &str) -> Option<&RegisteredCommand> {
+        self.commands.iter().find(|command| command.name == name)
+    }
+
+    #[cfg(feature = "enabled")]
+    /// Complete command lines starting with `input`
+    fn suggestions(&self, input: &str) -> Vec<String> {
+        let words: Vec<_> = input.split_whitespace().collect();
+        let typing_new_word = input.is_empty() || input.ends_with(char::is_whitespace);
+
+        match (words.split_first(), typing_new_word) {
+            (None, _) | (Some((_, [])), false) => {
+                let prefix = words.first().copied().unwrap_or("");
+                let mut names: Vec<_> = self.commands.iter().map(|command| command.name).collect();
+                names.extend(["help", "clear"]);
+                names
+                    .into_iter()
+                    .filter(|name| name.starts_with(prefix))
+                    .map(|name| format!("{name} "))
+                    .collect()
+            }
+            (Some((name, args)), _) => {
+                let Some(command) = self.command(name) else {
+                    return vec![];
+                };
+                let (previous, prefix) = match (args.split_last(), typing_new_word) {
+                    (Some((last, previous)), false) => (previous, *last),
+                    _ => (args, ""),
+                };
+                (command.complete)(previous)
+                    .into_iter()
+                    .filter(|value| value.starts_with(prefix))
+                    .map(|value| {
+                        let mut line = vec![*name];
+                        line.extend(previous);
+                        line.push(value.as_str());
+                        format!("{} ", line.join(" "))
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    #[cfg(feature = "enabled")]
+    fn execute(&self, data: &mut ConsoleData, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+        data.push(ConsoleLineKind::Input, format!("> {line}"));
+        data.push_history(line);
+
+        let words: Vec<_> = line.split_whitespace().collect();
+        let Some((name, args)) = words.split_first() else {
+            return;
+        };
+        match *name {
+            "help" => {
+                for command in &self.commands {
+                    let side = if command.server_side {
+                        "server"
+                    } else {
+                        "local"
+                    };
+                    data.push(ConsoleLineKind::Info, format!("{} ({side})", command.usage));
+                }
+            }
+            "clear" => data.lines.clear(),
+            _ => match self.command(name) {
+                Some(command) => match (command.run)(args) {
+                    Ok(pending) => data.pending.push(pending),
+                    Err(e) => data.push(
+                        ConsoleLineKind::Error,
+                        format!("{e}, usage: {}", command.usage),
+                    ),
+                },
+                None => data.push(
+                    ConsoleLineKind::Error,
+                    format!("Unknown command {name}, try help"),
+                ),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConsoleLineKind {
+    Input,
+    Info,
+    Error,
+}
+
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct ConsoleData {
+    pub input: String,
+    pub lines: Vec<(ConsoleLineKind, String)>,
+    /// Lines entered, oldest first
+    pub history: Vec<String>,
+    /// Position in `history` while browsing it with the arrow keys
+    #[cfg(feature = "enabled")]
+    #[serde(skip)]
+    history_cursor: Option<usize>,
+    #[serde(skip)]
+    pending: Vec<PendingCommand>,
+}
+
+impl ConsoleData {
+    pub fn push(&mut self, kind: ConsoleLineKind, text: String) {
+        if self.lines.len() == MAX_LINES {
+            self.lines.remove(0);
+        }
+        self.lines.push((kind, text));
+    }
+
+    /// Repeating the previous line doesn't add it again
+    fn push_history(&mut self, line: &str) {
+        if self.history.last().is_some_and(|last| last == line) {
+            return;
+        }
+        if self.history.len() == MAX_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push(line.to_string());
+    }
+}
+
+impl TypedDebugModule for Console {
+    type Data = ConsoleData;
+
+    fn add_protocol(&self, app: &mut App) {
+        for command in &self.commands {
+            (command.register)(app);
+        }
+    }
+
+    /// Runs outside of `set`: commands entered right before closing the console must still run
+    fn add_update_systems(&self, app: &mut App, _: DebugModuleSystems) {
+        app.add_systems(Last, run_pending_commands);
+    }
+
+    fn save_data(&self, data: &ConsoleData) -> Option<String> {
+        save_ron(data)
+    }
+
+    fn load_data(&self, saved: &str) -> Result<ConsoleData, String> {
+        load_ron(saved)
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: &ConsoleData) {
+        egui::ScrollArea::vertical()
+            .max_height(200.)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                for (kind, text) in &data.lines {
+                    let text = egui::RichText::new(text).monospace();
+                    match kind {
+                        ConsoleLineKind::Input => ui.label(text.strong()),
+                        ConsoleLineKind::Info => ui.label(text),
+                        ConsoleLineKind::Error => ui.label(text.color(egui::Color32::RED)),
+                    };
+                }
+            });
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui_mut(&self, ui: &mut egui::Ui, mut data: Mut<ConsoleData>) {
+        self.render_ui(ui, &data);
+
+        let mut input = data.input.clone();
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut input)
+                .desired_width(f32::INFINITY)
+                .font(egui::TextStyle::Monospace)
+                .hint_text("help")
+                // Tab completes instead of moving the focus
+                .lock_focus(true),
+        );
+        let suggestions = self.suggestions(&input);
+
+        if response.has_focus() {
+            let (tab, up, down) = ui.input(|i| {
+                (
+                    i.key_pressed(egui::Key::Tab),
+                    i.key_pressed(egui::Key::ArrowUp),
+                    i.key_pressed(egui::Key::ArrowDown),
+                )
+            });
+            if tab {
+                if let Some(suggestion) = suggestions.first() {
+                    input.clone_from(suggestion);
+                }
+            }
+            if up || down {
+                let cursor = match (data.history_cursor, up) {
+                    (None, true) => data.history.len().checked_sub(1),
+                    (Some(cursor), true) => Some(cursor.saturating_sub(1)),
+                    (Some(cursor), false) if cursor + 1 < data.history.len() => Some(cursor + 1),
+                    _ => None,
+                };
+                data.history_cursor = cursor;
+                input = cursor
+                    .map(|cursor| data.history[cursor].clone())
+                    .unwrap_or_default();
+            }
+        }
+
+        if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+            let data = &mut *data;
+            self.execute(data, input.trim());
+            data.history_cursor = None;
+            input.clear();
+            response.request_focus();
+        }
+
+        if !input.is_empty() {
+            ui.horizontal_wrapped(|ui| {
+                for suggestion in suggestions.iter().take(8) {
+                    if ui.small_button(suggestion.trim_end()).clicked() {
+                        input.clone_from(suggestion);
+                    }
+                }
+            });
+        }
+
+        if input != data.input {
+            data.input = input;
+        }
+    }
+}
+
+fn run_pending_commands(world: &mut World) {
+    if world.resource::<ConsoleData>().pending.is_empty() {
+        return;
+    }
+    let pending = std::mem::take(&mut world.resource_mut::<ConsoleData>().pending);
+    for command in pending {
+        command(world);
+    }
+}
+
+#[cfg(all(test, feature = "enabled"))]
+mod tests {
+    use super::*;
+
+    struct Spawn {
+        kind: String,
+        count: u32,
+    }
+
+    impl ConsoleCommand for Spawn {
+        const NAME: &'static str = "spawn";
+        const USAGE: &'static str = "spawn <kind> <count>";
+
+        fn parse(args: &[&str]) -> Result<Self, String> {
+            Ok(Self {
+                kind: parse_arg(args, 0, "kind")?,
+                count: parse_arg(args, 1, "count")?,
+            })
+        }
+
+        fn complete(args: &[&str]) -> Vec<String> {
+            match args {
+                [] => vec!["enemy".to_string(), "item".to_string()],
+                _ => vec![],
+            }
+        }
+    }
+
+    fn console() -> Console {
+        Console::new().with_local_command::<Spawn>()
+    }
+
+    fn last_line(data: &ConsoleData) -> &(ConsoleLineKind, String) {
+        data.lines.last().unwrap()
+    }
+
+    #[test]
+    fn completes_command_names() {
+        assert_eq!(console().suggestions("sp"), vec!["spawn "]);
+        assert_eq!(console().suggestions("c"), vec!["clear "]);
+        assert!(console().suggestions("x").is_empty());
+    }
+
+    #[test]
+    fn completes_arguments() {
+        assert_eq!(
+            console().suggestions("spawn "),
+            vec!["spawn enemy ", "spawn item "]
+        );
+        assert_eq!(console().suggestions("spawn e"), vec!["spawn enemy "]);
+        assert!(console().suggestions("spawn enemy ").is_empty());
+        assert!(console().suggestions("unknown ").is_empty());
+    }
+
+    #[test]
+    fn parses_arguments() {
+        let spawn = Spawn::parse(&["enemy", "5"]).unwrap();
+        assert_eq!(spawn.kind, "enemy");
+        assert_eq!(spawn.count, 5);
+        assert_eq!(
+            parse_arg::<u32>(&["enemy", "five"], 1, "count").unwrap_err(),
+            "invalid <count>: five"
+        );
+    }
+
+    #[test]
+    fn reports_missing_arguments() {
+        assert_eq!(
+            parse_arg::<u32>(&["enemy"], 1, "count").unwrap_err(),
+            "missing argument <count>"
+        );
+
+        let mut data = ConsoleData::default();
+        console().execute(&mut data, "spawn enemy");
+        assert_eq!(
+            last_line(&data),
+            &(
+                ConsoleLineKind::Error,
+                "missing argument <count>, usage: spawn <kind> <count>".to_string()
+            )
+        );
+        assert!(data.pending.is_empty());
+    }
+
+    #[test]
+    fn reports_unknown_commands() {
+        let mut data = ConsoleData::default();
+        console().execute(&mut data, "despawn enemy");
+        assert_eq!(
+            last_line(&data),
+            &(
+                ConsoleLineKind::Error,
+                "Unknown command despawn, try help".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn queues_valid_commands() {
+        let mut data = ConsoleData::default();
+        console().execute(&mut data, "spawn enemy 5");
+        assert_eq!(data.pending.len(), 1);
+    }
+
+    #[test]
+    fn history_skips_repeats_and_is_capped() {
+        let mut data = ConsoleData::default();
+        console().execute(&mut data, "help");
+        console().execute(&mut data, "help");
+        assert_eq!(data.history, vec!["help"]);
+
+        for i in 0..MAX_HISTORY + 10 {
+            console().execute(&mut data, &format!("spawn enemy {i}"));
+        }
+        assert_eq!(data.history.len(), MAX_HISTORY);
+        assert_eq!(
+            data.history.last().unwrap(),
+            &format!("spawn enemy {}", MAX_HISTORY + 9)
+        );
+    }
+}
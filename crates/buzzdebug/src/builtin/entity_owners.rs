@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::common_conditions::on_real_timer};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+use lightyear::prelude::{
+    server::{ConnectionManager, ControlledBy},
+    AppComponentExt, ChannelDirection, ClientId, Replicating,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{typed::TypedDebugModule, viewing::DebugModuleSystems};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Which clients control each replicated entity of the server. The owners are replicated to the
+/// clients as an [`EntityOwner`] component on the entities themselves, so that lightyear maps
+/// them to the client entities, and the
+/// [`ReplicationInspector`](super::ReplicationInspector) shows them.
+pub struct EntityOwners;
+
+/// Clients controlling a replicated entity, according to its `ControlledBy` on the server. Empty
+/// for entities controlled by the server.
+#[derive(Component, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EntityOwner(pub Vec<ClientId>);
+
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EntityOwnersData {
+    /// Number of replicated entities controlled by each client
+    pub clients: Vec<(ClientId, usize)>,
+    /// Number of replicated entities controlled by the server
+    pub server: usize,
+}
+
+impl TypedDebugModule for EntityOwners {
+    type Data = EntityOwnersData;
+
+    fn add_protocol(&self, app: &mut App) {
+        app.register_component::<EntityOwner>(ChannelDirection::ServerToClient);
+    }
+
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems) {
+        app.add_systems(
+            Last,
+            sample_entity_owners
+                .run_if(on_real_timer(SAMPLE_INTERVAL))
+                .in_set(set),
+        );
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: &EntityOwnersData) {
+        egui::Grid::new("entity_owners")
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Owner");
+                ui.strong("Entities");
+                ui.end_row();
+                ui.label("Server");
+                ui.label(data.server.to_string());
+                ui.end_row();
+                for (client_id, entities) in &data.clients {
+                    ui.label(format!("{client_id:?}"));
+                    ui.label(entities.to_string());
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+fn sample_entity_owners(
+    mut commands: Commands,
+    replicated: Query<(Entity, Option<&ControlledBy>, Option<&EntityOwner>), With<Replicating>>,
+    connection_manager: Res<ConnectionManager>,
+    mut data: ResMut<EntityOwnersData>,
+) {
+    let clients: Vec<_> = connection_manager.connected_clients().collect();
+    let mut owned = vec![0; clients.len()];
+    let mut server = 0;
+
+    for (entity, controlled_by, current) in &replicated {
+        let owner = EntityOwner(
+            clients
+                .iter()
+                .copied()
+                .filter(|client_id| {
+                    controlled_by
+                        .is_some_and(|controlled_by| controlled_by.target.targets(client_id))
+                })
+                .collect(),
+        );
+
+        if owner.0.is_empty() {
+            server += 1;
+        }
+        for (count, client_id) in owned.iter_mut().zip(&clients) {
+            if owner.0.contains(client_id) {
+                *count += 1;
+            }
+        }
+        // Only inserted when it changes, so that it isn't replicated again every sample
+        if current != Some(&owner) {
+            commands.entity(entity).insert(owner);
+        }
+    }
+
+    data.clients = clients.into_iter().zip(owned).collect();
+    data.server = server;
+}
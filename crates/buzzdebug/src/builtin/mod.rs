@@ -1,2 +1,30 @@
+mod bandwidth;
+mod console;
+mod entity_owners;
+mod network_stats;
+mod prediction_settings;
+mod replication_inspector;
+mod rollbacks;
+mod server_performance;
+mod server_positions;
+mod time_control;
 
-
+pub use bandwidth::{
+    BandwidthRow, ClientBandwidth, ReplicationBandwidth, ReplicationBandwidthData,
+};
+pub use console::{
+    parse_arg, Console, ConsoleCommand, ConsoleData, ConsoleLineKind, LocalConsoleCommand,
+};
+pub use entity_owners::{EntityOwner, EntityOwners, EntityOwnersData};
+pub use network_stats::{NetworkStats, NetworkStatsData};
+pub use prediction_settings::PredictionSettingsModule;
+pub use replication_inspector::{
+    ComponentValue, ComponentValues, InspectedEntity, ReplicationInspector,
+    ReplicationInspectorData,
+};
+pub use rollbacks::{EntityRollbacks, RollbackEvent, RollbackVisualizer, RollbackVisualizerData};
+pub use server_performance::{
+    ClientStats, DiagnosticSample, ServerPerformance, ServerPerformanceData,
+};
+pub use server_positions::{ServerPosition, ServerPositions, ServerPositionsData};
+pub use time_control::{TimeControl, TimeControlCommand, TimeControlData};
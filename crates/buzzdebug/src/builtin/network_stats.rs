@@ -0,0 +1,332 @@
+use std::time::Duration;
+
+use bevy::{
+    diagnostic::DiagnosticsStore, prelude::*, time::common_conditions::on_real_timer,
+    utils::HashMap,
+};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+use lightyear::{
+    client::{config::ClientConfig, prediction::diagnostics::PredictionDiagnosticsPlugin},
+    connection::client::NetConfig,
+    prelude::{
+        client::{self, Confirmed, ConnectionManager},
+        server, *,
+    },
+    transport::io::IoDiagnosticsPlugin,
+};
+use serde::{Deserialize, Serialize};
+use zusammen_app::AdmittedClients;
+
+#[cfg(feature = "enabled")]
+use crate::widgets::history_row;
+use crate::{
+    recording::{load_ron, save_ron},
+    typed::TypedDebugModule,
+    viewing::DebugModuleSystems,
+    widgets::History,
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+/// 20 seconds worth of samples
+const HISTORY_LEN: usize = 200;
+/// Between the probes the server sends to measure packet loss
+const PROBE_INTERVAL: Duration = Duration::from_millis(50);
+/// Between the requests for probes the client sends while the module is enabled
+const PROBE_REQUEST_INTERVAL: Duration = Duration::from_millis(500);
+/// The server stops sending probes to a client this long after its latest request
+const PROBE_REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+/// Packet loss is measured over this many probes, a single sample would be too noisy
+const PROBE_WINDOW: u32 = 40;
+
+/// Connection metrics of the client: RTT, jitter, traffic, ticks and rollbacks.
+///
+/// Lightyear doesn't measure packet loss, so the server sends numbered probes through an
+/// unreliable channel, and the client counts the missing ones. Probes are only sent to admitted
+/// clients that keep asking for them, i.e. while the module is enabled there. The traffic and
+/// rollback graphs need lightyear's `IoDiagnosticsPlugin` and `PredictionDiagnosticsPlugin`.
+pub struct NetworkStats;
+
+#[derive(Channel)]
+struct LossProbeChannel;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct LossProbe(u32);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct LossProbeRequest;
+
+/// Latest probe request of each client, on the server
+#[derive(Resource, Default)]
+struct ProbedClients(HashMap<ClientId, Duration>);
+
+/// Probes received since the last packet loss measurement
+#[derive(Resource, Default)]
+struct LossProbes {
+    first: Option<u32>,
+    latest: u32,
+    received: u32,
+}
+
+#[derive(Resource, Serialize, Deserialize)]
+pub struct NetworkStatsData {
+    pub rtt_ms: History,
+    pub jitter_ms: History,
+    pub kb_in_per_sec: History,
+    pub kb_out_per_sec: History,
+    /// Measured from server to client, in percent
+    pub packet_loss: History,
+    pub rollbacks: History,
+    /// Whether the diagnostics the traffic and rollback graphs are made from are available
+    pub io_diagnostics: bool,
+    pub prediction_diagnostics: bool,
+    pub tick: Tick,
+    /// Latest server tick confirmed by the replication of any entity
+    pub confirmed_server_tick: Option<Tick>,
+    pub input_delay_ticks: u16,
+    /// Packet loss of the link conditioner
+    pub simulated_packet_loss: Option<f32>,
+}
+
+impl Default for NetworkStatsData {
+    fn default() -> Self {
+        Self {
+            rtt_ms: History::new(HISTORY_LEN),
+            jitter_ms: History::new(HISTORY_LEN),
+            kb_in_per_sec: History::new(HISTORY_LEN),
+            kb_out_per_sec: History::new(HISTORY_LEN),
+            packet_loss: History::new(HISTORY_LEN),
+            rollbacks: History::new(HISTORY_LEN),
+            io_diagnostics: false,
+            prediction_diagnostics: false,
+            tick: default(),
+            confirmed_server_tick: None,
+            input_delay_ticks: 0,
+            simulated_packet_loss: None,
+        }
+    }
+}
+
+impl TypedDebugModule for NetworkStats {
+    type Data = NetworkStatsData;
+
+    fn add_protocol(&self, app: &mut App) {
+        app.add_channel::<LossProbeChannel>(ChannelSettings {
+            mode: ChannelMode::UnorderedUnreliable,
+            ..default()
+        });
+        app.register_message::<LossProbe>(ChannelDirection::ServerToClient);
+        app.register_message::<LossProbeRequest>(ChannelDirection::ClientToServer);
+    }
+
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems) {
+        app.init_resource::<LossProbes>();
+        app.add_systems(
+            Update,
+            (
+                request_loss_probes.run_if(
+                    in_state(client::NetworkingState::Connected)
+                        .and_then(on_real_timer(PROBE_REQUEST_INTERVAL)),
+                ),
+                receive_loss_probes,
+                sample_network_stats.run_if(on_real_timer(SAMPLE_INTERVAL)),
+            )
+                .chain()
+                .in_set(set),
+        );
+    }
+
+    /// Sends the packet loss probes
+    fn add_server_systems(&self, app: &mut App) {
+        app.init_resource::<ProbedClients>();
+        app.add_systems(
+            Update,
+            (
+                receive_probe_requests,
+                send_loss_probes.run_if(on_real_timer(PROBE_INTERVAL)),
+            )
+                .chain(),
+        );
+    }
+
+    /// Cheap enough to keep the history filled while the panel is closed
+    fn sample_always(&self) -> bool {
+        true
+    }
+
+    fn save_data(&self, data: &NetworkStatsData) -> Option<String> {
+        save_ron(data)
+    }
+
+    fn load_data(&self, saved: &str) -> Result<NetworkStatsData, String> {
+        load_ron(saved)
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: &NetworkStatsData) {
+        ui.label(format!("Tick: {}", data.tick.0));
+        match data.confirmed_server_tick {
+            Some(tick) => ui.label(format!("Latest confirmed server tick: {}", tick.0)),
+            None => ui.label("Latest confirmed server tick: -"),
+        };
+        ui.label(format!("Input delay: {} ticks", data.input_delay_ticks));
+        match data.simulated_packet_loss {
+            Some(loss) => ui.label(format!("Simulated packet loss: {:.1}%", loss * 100.)),
+            None => ui.label("Simulated packet loss: none"),
+        };
+
+        ui.separator();
+        history_row(ui, "RTT", &data.rtt_ms, "ms");
+        history_row(ui, "Jitter", &data.jitter_ms, "ms");
+        history_row(ui, "Packet loss", &data.packet_loss, "%");
+        if data.io_diagnostics {
+            history_row(ui, "In", &data.kb_in_per_sec, "KB/s");
+            history_row(ui, "Out", &data.kb_out_per_sec, "KB/s");
+        } else {
+            missing_diagnostics(ui, "In/Out", "IoDiagnosticsPlugin");
+        }
+        if data.prediction_diagnostics {
+            history_row(ui, "Rollbacks", &data.rollbacks, "/s");
+        } else {
+            missing_diagnostics(ui, "Rollbacks", "PredictionDiagnosticsPlugin");
+        }
+    }
+}
+
+#[cfg(feature = "enabled")]
+fn missing_diagnostics(ui: &mut egui::Ui, label: &str, plugin: &str) {
+    ui.label(format!("{label}: -")).on_hover_text(format!(
+        "Add lightyear's {plugin} to the client to measure this"
+    ));
+}
+
+fn request_loss_probes(mut connection: ResMut<ConnectionManager>) {
+    if let Err(e) = connection.send_message::<LossProbeChannel, _>(&LossProbeRequest) {
+        error!("Could not request packet loss probes: {e:?}");
+    }
+}
+
+fn receive_probe_requests(
+    mut requests: EventReader<server::MessageEvent<LossProbeRequest>>,
+    mut probed: ResMut<ProbedClients>,
+    admitted: Res<AdmittedClients>,
+    time: Res<Time<Real>>,
+) {
+    for request in requests.read() {
+        let client_id = *request.context();
+        if admitted.contains(client_id) {
+            probed.0.insert(client_id, time.elapsed());
+        }
+    }
+}
+
+fn send_loss_probes(
+    mut connection: ResMut<server::ConnectionManager>,
+    mut probed: ResMut<ProbedClients>,
+    admitted: Res<AdmittedClients>,
+    time: Res<Time<Real>>,
+    mut next: Local<u32>,
+) {
+    let now = time.elapsed();
+    probed.0.retain(|client_id, requested_at| {
+        admitted.contains(*client_id) && now.saturating_sub(*requested_at) < PROBE_REQUEST_TIMEOUT
+    });
+    if probed.0.is_empty() {
+        return;
+    }
+
+    let target = NetworkTarget::Only(probed.0.keys().copied().collect());
+    if let Err(e) =
+        connection.send_message_to_target::<LossProbeChannel, _>(&LossProbe(*next), target)
+    {
+        error!("Could not send packet loss probe: {e:?}");
+    }
+    *next = next.wrapping_add(1);
+}
+
+fn receive_loss_probes(
+    mut messages: EventReader<client::MessageEvent<LossProbe>>,
+    mut probes: ResMut<LossProbes>,
+) {
+    for message in messages.read() {
+        let LossProbe(id) = *message.message();
+        // Probes aren't counted while the module is disabled, the window starts over after that
+        let ahead = id.wrapping_sub(probes.latest);
+        if probes.first.is_some() && (4 * PROBE_WINDOW..u32::MAX / 2).contains(&ahead) {
+            *probes = default();
+        }
+        let first = *probes.first.get_or_insert(id);
+        // Probes can be reordered, and ones from before the window started are ignored
+        if id.wrapping_sub(first) < u32::MAX / 2 {
+            probes.latest = probes.latest.max(id);
+            probes.received += 1;
+        }
+    }
+}
+
+/// Percentage of probes lost, once a full window of them was expected
+fn measure_packet_loss(probes: &mut LossProbes) -> Option<f32> {
+    let expected = probes.latest.wrapping_sub(probes.first?) + 1;
+    if expected < PROBE_WINDOW {
+        return None;
+    }
+    let lost = expected.saturating_sub(probes.received);
+    *probes = default();
+    Some(100. * lost as f32 / expected as f32)
+}
+
+fn diagnostic_value(
+    diagnostics: Option<&DiagnosticsStore>,
+    path: &bevy::diagnostic::DiagnosticPath,
+) -> Option<f32> {
+    diagnostics?.get(path)?.smoothed().map(|value| value as f32)
+}
+
+fn sample_network_stats(
+    connection: Res<ConnectionManager>,
+    client_config: Res<ClientConfig>,
+    tick_manager: Res<TickManager>,
+    confirmed: Query<&Confirmed>,
+    diagnostics: Option<Res<DiagnosticsStore>>,
+    mut probes: ResMut<LossProbes>,
+    mut data: ResMut<NetworkStatsData>,
+) {
+    let diagnostics = diagnostics.as_deref();
+    let rtt = connection.rtt();
+
+    data.rtt_ms.push(rtt.as_secs_f32() * 1000.);
+    data.jitter_ms
+        .push(connection.jitter().as_secs_f32() * 1000.);
+    if let Some(packet_loss) = measure_packet_loss(&mut probes) {
+        data.packet_loss.push(packet_loss);
+    }
+
+    // The diagnostics are in bytes per second
+    let bytes_in = diagnostic_value(diagnostics, &IoDiagnosticsPlugin::BYTES_IN);
+    let bytes_out = diagnostic_value(diagnostics, &IoDiagnosticsPlugin::BYTES_OUT);
+    data.io_diagnostics = bytes_in.is_some() || bytes_out.is_some();
+    if let Some(bytes_in) = bytes_in {
+        data.kb_in_per_sec.push(bytes_in / 1000.);
+    }
+    if let Some(bytes_out) = bytes_out {
+        data.kb_out_per_sec.push(bytes_out / 1000.);
+    }
+    let rollbacks = diagnostic_value(diagnostics, &PredictionDiagnosticsPlugin::ROLLBACKS);
+    data.prediction_diagnostics = rollbacks.is_some();
+    if let Some(rollbacks) = rollbacks {
+        data.rollbacks.push(rollbacks);
+    }
+
+    data.tick = tick_manager.tick();
+    data.confirmed_server_tick = confirmed.iter().map(|confirmed| confirmed.tick).max();
+    data.input_delay_ticks = client_config
+        .prediction
+        .input_delay_ticks(rtt, client_config.shared.tick.tick_duration);
+    data.simulated_packet_loss = match &client_config.net {
+        NetConfig::Netcode { io, .. } => io
+            .conditioner
+            .as_ref()
+            .map(|conditioner| conditioner.incoming_loss),
+        _ => None,
+    };
+}
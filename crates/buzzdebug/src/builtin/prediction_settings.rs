@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+use lightyear::client::config::ClientConfig;
+use zusammen_app::ClientPredictionSettings;
+
+use crate::{typed::TypedDebugModule, viewing::DebugModuleSystems};
+
+#[cfg(feature = "enabled")]
+const MAX_INPUT_DELAY_TICKS: u16 = 30;
+#[cfg(feature = "enabled")]
+const MAX_CORRECTION_TICKS_FACTOR: f32 = 5.;
+
+/// Shows and edits the client's [`ClientPredictionSettings`]
+pub struct PredictionSettingsModule;
+
+impl TypedDebugModule for PredictionSettingsModule {
+    type Data = ClientPredictionSettings;
+
+    /// Lightyear's defaults, there is no `ClientConfig` to read the settings from
+    fn insert_standalone_data(&self, app: &mut App) {
+        let prediction = ClientConfig::default().prediction;
+        app.insert_resource(ClientPredictionSettings {
+            maximum_input_delay_ticks: prediction.maximum_input_delay_before_prediction,
+            correction_ticks_factor: prediction.correction_ticks_factor,
+        });
+    }
+
+    fn add_update_systems(&self, _: &mut App, _: DebugModuleSystems) {}
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, settings: &ClientPredictionSettings) {
+        ui.label(format!(
+            "Maximum input delay: {} ticks",
+            settings.maximum_input_delay_ticks
+        ));
+        ui.label(format!(
+            "Correction ticks factor: {:.2}",
+            settings.correction_ticks_factor
+        ));
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui_mut(&self, ui: &mut egui::Ui, mut settings: Mut<ClientPredictionSettings>) {
+        let mut edited = settings.clone();
+
+        ui.add(
+            egui::Slider::new(
+                &mut edited.maximum_input_delay_ticks,
+                0..=MAX_INPUT_DELAY_TICKS,
+            )
+            .text("Maximum input delay ticks"),
+        );
+        ui.add(
+            egui::Slider::new(
+                &mut edited.correction_ticks_factor,
+                0.0..=MAX_CORRECTION_TICKS_FACTOR,
+            )
+            .text("Correction ticks factor"),
+        );
+
+        settings.set_if_neq(edited);
+    }
+}
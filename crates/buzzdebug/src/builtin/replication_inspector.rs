@@ -0,0 +1,358 @@
+use std::{
+    fmt::{self, Debug},
+    time::Duration,
+};
+
+use bevy::{prelude::*, time::common_conditions::on_real_timer, utils::get_short_name};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::{egui, reflect_inspector::ui_for_value_readonly};
+use lightyear::prelude::{
+    client::{Confirmed, Interpolated, Predicted},
+    Controlled, Replicating,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::EntityOwner;
+use crate::{
+    recording::{load_ron, save_ron},
+    typed::TypedDebugModule,
+    viewing::DebugModuleSystems,
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+/// Of the marker drawn at each entity's position
+const GIZMO_RADIUS: f32 = 0.25;
+
+/// Lists the replicated entities of the client, with their replication markers. For the
+/// components registered with [`ReplicationInspector::with_component`], shows the values of
+/// predicted and interpolated entities next to the ones of their confirmed entity, with the
+/// reflection-based inspector of `bevy_inspector_egui`.
+///
+/// Its gizmos mark the position of every entity with a `Transform`, with a line from predicted and
+/// interpolated entities to their confirmed position.
+///
+/// Lightyear only tells a client which entities it controls itself. The owners of the other
+/// entities are shown when the server has the [`EntityOwners`](super::EntityOwners) module.
+#[derive(Clone, Default)]
+pub struct ReplicationInspector {
+    components: Vec<InspectedComponent>,
+}
+
+#[derive(Clone)]
+struct InspectedComponent {
+    name: &'static str,
+    reflect: fn(&EntityRef) -> Option<ComponentValue>,
+}
+
+impl ReplicationInspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `C` needs to be registered in the app's type registry for its fields to be shown
+    pub fn with_component<C: Component + Reflect>(mut self) -> Self {
+        self.components.push(InspectedComponent {
+            name: std::any::type_name::<C>(),
+            reflect: |entity| {
+                entity
+                    .get::<C>()
+                    .map(|component| ComponentValue::Reflected(component.clone_value()))
+            },
+        });
+        self
+    }
+}
+
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+pub struct ReplicationInspectorData {
+    pub entities: Vec<InspectedEntity>,
+    /// Registry of the app, to show the component values
+    #[serde(skip)]
+    pub type_registry: AppTypeRegistry,
+}
+
+impl Debug for ReplicationInspectorData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReplicationInspectorData")
+            .field("entities", &self.entities)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InspectedEntity {
+    pub entity: Entity,
+    pub replicating: bool,
+    pub predicted: bool,
+    pub interpolated: bool,
+    pub confirmed: bool,
+    pub controlled: bool,
+    /// Clients controlling the entity, if the server streams them with
+    /// [`EntityOwners`](super::EntityOwners)
+    pub owner: Option<EntityOwner>,
+    /// Confirmed entity of a predicted or interpolated entity
+    pub confirmed_entity: Option<Entity>,
+    /// Translation of the entity's `Transform`
+    pub position: Option<Vec3>,
+    /// Translation of the confirmed entity's `Transform`
+    pub confirmed_position: Option<Vec3>,
+    pub components: Vec<ComponentValues>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ComponentValues {
+    pub name: String,
+    pub value: Option<ComponentValue>,
+    /// Value on the confirmed entity, if there is one
+    pub confirmed_value: Option<ComponentValue>,
+}
+
+/// Value of a component. Recordings only keep its `Debug` text, which is what they play back.
+pub enum ComponentValue {
+    /// Copy made through reflection
+    Reflected(Box<dyn Reflect>),
+    Text(String),
+}
+
+impl Clone for ComponentValue {
+    fn clone(&self) -> Self {
+        match self {
+            ComponentValue::Reflected(value) => ComponentValue::Reflected(value.clone_value()),
+            ComponentValue::Text(text) => ComponentValue::Text(text.clone()),
+        }
+    }
+}
+
+impl Debug for ComponentValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComponentValue::Reflected(value) => value.debug(f),
+            ComponentValue::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+impl Serialize for ComponentValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{self:?}"))
+    }
+}
+
+impl<'de> Deserialize<'de> for ComponentValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(ComponentValue::Text)
+    }
+}
+
+impl InspectedEntity {
+    #[cfg(feature = "enabled")]
+    fn markers(&self) -> String {
+        [
+            (self.replicating, "Replicating"),
+            (self.predicted, "Predicted"),
+            (self.interpolated, "Interpolated"),
+            (self.confirmed, "Confirmed"),
+        ]
+        .into_iter()
+        .filter_map(|(present, marker)| present.then_some(marker))
+        .collect::<Vec<_>>()
+        .join(", ")
+    }
+
+    #[cfg(feature = "enabled")]
+    fn owner(&self) -> String {
+        match &self.owner {
+            Some(owner) if owner.0.is_empty() => "server".to_string(),
+            Some(owner) => {
+                let clients = owner
+                    .0
+                    .iter()
+                    .map(|client_id| format!("{client_id:?}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if self.controlled {
+                    format!("{clients} (including this client)")
+                } else {
+                    clients
+                }
+            }
+            None if self.controlled => "this client".to_string(),
+            None => "unknown".to_string(),
+        }
+    }
+
+    fn gizmo_color(&self) -> Color {
+        if self.predicted {
+            Color::srgb(0.2, 1., 0.2)
+        } else if self.interpolated {
+            Color::srgb(0.2, 0.6, 1.)
+        } else if self.confirmed {
+            Color::srgb(1., 0.2, 0.2)
+        } else {
+            Color::WHITE
+        }
+    }
+}
+
+/// Components registered with the inspector, read by its update system
+#[derive(Resource, Clone)]
+struct InspectedComponents(Vec<InspectedComponent>);
+
+impl TypedDebugModule for ReplicationInspector {
+    type Data = ReplicationInspectorData;
+
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems) {
+        app.insert_resource(InspectedComponents(self.components.clone()));
+        app.add_systems(
+            Update,
+            sample_replicated_entities
+                .run_if(on_real_timer(SAMPLE_INTERVAL))
+                .in_set(set),
+        );
+    }
+
+    fn save_data(&self, data: &ReplicationInspectorData) -> Option<String> {
+        save_ron(data)
+    }
+
+    fn load_data(&self, saved: &str) -> Result<ReplicationInspectorData, String> {
+        load_ron(saved)
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: &ReplicationInspectorData) {
+        ui.label(format!("Replicated entities: {}", data.entities.len()));
+
+        let type_registry = data.type_registry.read();
+        for entity in &data.entities {
+            egui::CollapsingHeader::new(format!("{:?} [{}]", entity.entity, entity.markers()))
+                .id_source(entity.entity)
+                .show(ui, |ui| {
+                    ui.label(format!("Owner: {}", entity.owner()));
+                    if let Some(confirmed) = entity.confirmed_entity {
+                        ui.label(format!("Confirmed entity: {confirmed:?}"));
+                    }
+                    if entity.components.is_empty() {
+                        return;
+                    }
+
+                    egui::Grid::new(("replication_inspector", entity.entity))
+                        .striped(true)
+                        .show(ui, |ui| {
+                            ui.strong("Component");
+                            ui.strong("Value");
+                            ui.strong("Confirmed");
+                            ui.end_row();
+                            for component in &entity.components {
+                                ui.label(&component.name);
+                                for value in [&component.value, &component.confirmed_value] {
+                                    match value {
+                                        Some(ComponentValue::Reflected(value)) => {
+                                            ui_for_value_readonly(
+                                                value.as_ref(),
+                                                ui,
+                                                &type_registry,
+                                            );
+                                        }
+                                        Some(ComponentValue::Text(text)) => {
+                                            ui.label(text);
+                                        }
+                                        None => {
+                                            ui.label("-");
+                                        }
+                                    }
+                                }
+                                ui.end_row();
+                            }
+                        });
+                });
+        }
+    }
+
+    fn has_gizmos(&self) -> bool {
+        true
+    }
+
+    fn draw_gizmos(&self, gizmos: &mut Gizmos, data: &ReplicationInspectorData) {
+        for entity in &data.entities {
+            let Some(position) = entity.position else {
+                continue;
+            };
+            let color = entity.gizmo_color();
+            gizmos.sphere(position, Quat::IDENTITY, GIZMO_RADIUS, color);
+            if let Some(confirmed_position) = entity.confirmed_position {
+                gizmos.line(position, confirmed_position, color);
+            }
+        }
+    }
+}
+
+fn sample_replicated_entities(world: &mut World) {
+    let inspected = world.resource::<InspectedComponents>().clone();
+    let mut query = world.query_filtered::<EntityRef, Or<(
+        With<Replicating>,
+        With<Predicted>,
+        With<Interpolated>,
+        With<Confirmed>,
+    )>>();
+
+    let mut entities: Vec<_> = query
+        .iter(world)
+        .map(|entity_ref| {
+            let confirmed_entity = entity_ref
+                .get::<Predicted>()
+                .and_then(|predicted| predicted.confirmed_entity)
+                .or_else(|| {
+                    entity_ref
+                        .get::<Interpolated>()
+                        .map(|interpolated| interpolated.confirmed_entity)
+                });
+            let confirmed_ref = confirmed_entity.and_then(|entity| world.get_entity(entity));
+            let position = |entity: &EntityRef| {
+                entity
+                    .get::<Transform>()
+                    .map(|transform| transform.translation)
+            };
+
+            let owner = entity_ref
+                .get::<EntityOwner>()
+                .or_else(|| confirmed_ref.as_ref()?.get::<EntityOwner>())
+                .cloned();
+
+            InspectedEntity {
+                entity: entity_ref.id(),
+                replicating: entity_ref.contains::<Replicating>(),
+                predicted: entity_ref.contains::<Predicted>(),
+                interpolated: entity_ref.contains::<Interpolated>(),
+                confirmed: entity_ref.contains::<Confirmed>(),
+                controlled: entity_ref.contains::<Controlled>(),
+                owner,
+                confirmed_entity,
+                position: position(&entity_ref),
+                confirmed_position: confirmed_ref.as_ref().and_then(position),
+                components: inspected
+                    .0
+                    .iter()
+                    .filter_map(|component| {
+                        let value = (component.reflect)(&entity_ref);
+                        let confirmed_value = confirmed_ref
+                            .as_ref()
+                            .and_then(|confirmed| (component.reflect)(confirmed));
+                        (value.is_some() || confirmed_value.is_some()).then(|| ComponentValues {
+                            name: get_short_name(component.name),
+                            value,
+                            confirmed_value,
+                        })
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    entities.sort_by_key(|entity| entity.entity);
+
+    let type_registry = world.resource::<AppTypeRegistry>().clone();
+    let mut data = world.resource_mut::<ReplicationInspectorData>();
+    data.entities = entities;
+    data.type_registry = type_registry;
+}
@@ -0,0 +1,382 @@
+use std::{
+    any::Any,
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+
+use bevy::{prelude::*, utils::get_short_name};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+use lightyear::prelude::{
+    client::{ComponentState, Confirmed, Predicted, PredictionHistory, PredictionSet, Rollback},
+    Tick, TickManager,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    recording::{load_ron, save_ron},
+    typed::TypedDebugModule,
+    viewing::DebugModuleSystems,
+};
+
+/// Rollback events kept in the list and the timeline
+const MAX_EVENTS: usize = 100;
+#[cfg(feature = "enabled")]
+/// Ticks shown by the timeline, up to the current one
+const TIMELINE_TICKS: i16 = 600;
+/// Smaller corrections are considered exact predictions
+const MIN_CORRECTION: f32 = 1e-4;
+/// Of the markers drawn at the predicted and confirmed positions
+const GIZMO_RADIUS: f32 = 0.25;
+
+/// Shows when rollbacks happen and what they correct, e.g. for entities simulated on the client
+/// with `misc_macros::simulated_server_auth!`. For the components registered with
+/// [`RollbackVisualizer::with_component`], every rollback caused by a misprediction of their value
+/// on a predicted entity is recorded with its tick and the size of the correction.
+///
+/// Its gizmos draw the confirmed "ghost" of each predicted entity with a `Transform`, linked to
+/// the predicted position.
+#[derive(Clone, Default)]
+pub struct RollbackVisualizer {
+    components: Vec<TrackedComponent>,
+}
+
+#[derive(Clone)]
+struct TrackedComponent {
+    name: &'static str,
+    /// Whether the value predicted for the tick of the confirmed entity differs from the confirmed
+    /// one, i.e. whether this component failed the prediction check
+    mispredicted: Arc<dyn Fn(&EntityRef, &EntityRef, Tick) -> bool + Send + Sync>,
+    /// Copy of the component, compared after the rollback
+    snapshot: fn(&EntityRef) -> Option<Box<dyn Any + Send + Sync>>,
+    /// Size of the difference between the snapshot and the current value
+    correction: Arc<dyn Fn(&dyn Any, &EntityRef) -> Option<f32> + Send + Sync>,
+}
+
+impl RollbackVisualizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `correction` measures the difference between the predicted value of `C` and the one
+    /// after rolling back, e.g. `|a: &Position, b: &Position| a.0.distance(b.0)`
+    pub fn with_component<C: Component + Clone>(
+        mut self,
+        correction: impl Fn(&C, &C) -> f32 + Send + Sync + 'static,
+    ) -> Self {
+        let correction = Arc::new(correction);
+        let measure = correction.clone();
+        self.components.push(TrackedComponent {
+            name: std::any::type_name::<C>(),
+            mispredicted: Arc::new(move |predicted, confirmed, tick| {
+                let Some(history) = predicted.get::<PredictionHistory<C>>() else {
+                    return false;
+                };
+                let predicted = match history.get(tick) {
+                    Some(ComponentState::Updated(value)) => Some(value),
+                    Some(ComponentState::Removed) | None => None,
+                };
+                match (predicted, confirmed.get::<C>()) {
+                    (Some(predicted), Some(confirmed)) => {
+                        measure(predicted, confirmed) > MIN_CORRECTION
+                    }
+                    (None, None) => false,
+                    _ => true,
+                }
+            }),
+            snapshot: |entity| {
+                entity
+                    .get::<C>()
+                    .map(|component| Box::new(component.clone()) as Box<dyn Any + Send + Sync>)
+            },
+            correction: Arc::new(move |before, entity| {
+                let before = before.downcast_ref::<C>()?;
+                Some(correction(before, entity.get::<C>()?))
+            }),
+        });
+        self
+    }
+}
+
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RollbackVisualizerData {
+    pub current_tick: Option<Tick>,
+    pub total_rollbacks: u64,
+    /// Latest rollbacks that corrected a component, oldest first
+    pub events: VecDeque<RollbackEvent>,
+    /// Rollbacks of the entities that still exist
+    pub entities: BTreeMap<Entity, EntityRollbacks>,
+    /// Predicted and confirmed position of each predicted entity
+    pub ghosts: Vec<(Vec3, Vec3)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RollbackEvent {
+    /// Tick the client rolled back to
+    pub tick: Tick,
+    pub entity: Entity,
+    pub component: String,
+    pub correction: f32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EntityRollbacks {
+    pub corrections: u32,
+    pub largest_correction: f32,
+}
+
+impl RollbackVisualizerData {
+    fn push(&mut self, event: RollbackEvent) {
+        let entity = self.entities.entry(event.entity).or_default();
+        entity.corrections += 1;
+        entity.largest_correction = entity.largest_correction.max(event.correction);
+
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+}
+
+/// Components registered with the visualizer, read by its update systems
+#[derive(Resource, Clone)]
+struct TrackedComponents(Vec<TrackedComponent>);
+
+/// Entity, index of the tracked component and its predicted value
+type Snapshot = (Entity, usize, Box<dyn Any + Send + Sync>);
+
+/// Values of the mispredicted components right before the rollback
+#[derive(Resource, Default)]
+struct PendingRollback {
+    mispredicted: Vec<Snapshot>,
+    rollback: Option<(Tick, Vec<Snapshot>)>,
+}
+
+impl TypedDebugModule for RollbackVisualizer {
+    type Data = RollbackVisualizerData;
+
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems) {
+        app.insert_resource(TrackedComponents(self.components.clone()));
+        app.init_resource::<PendingRollback>();
+        app.add_systems(
+            PreUpdate,
+            (
+                find_mispredictions.before(PredictionSet::CheckRollback),
+                snapshot_before_rollback
+                    .after(PredictionSet::CheckRollback)
+                    .before(PredictionSet::PrepareRollback),
+                measure_corrections.after(PredictionSet::Rollback),
+            )
+                .in_set(set),
+        );
+    }
+
+    /// Cheap enough to keep the history filled while the panel is closed
+    fn sample_always(&self) -> bool {
+        true
+    }
+
+    fn save_data(&self, data: &RollbackVisualizerData) -> Option<String> {
+        save_ron(data)
+    }
+
+    fn load_data(&self, saved: &str) -> Result<RollbackVisualizerData, String> {
+        load_ron(saved)
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: &RollbackVisualizerData) {
+        ui.label(format!("Rollbacks: {}", data.total_rollbacks));
+        if let Some(current_tick) = data.current_tick {
+            timeline(ui, current_tick, &data.events);
+        }
+
+        egui::CollapsingHeader::new(format!("Entities ({})", data.entities.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                egui::Grid::new("rollback_entities")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.strong("Entity");
+                        ui.strong("Corrections");
+                        ui.strong("Largest");
+                        ui.end_row();
+                        for (entity, rollbacks) in &data.entities {
+                            ui.label(format!("{entity:?}"));
+                            ui.label(rollbacks.corrections.to_string());
+                            ui.label(format!("{:.3}", rollbacks.largest_correction));
+                            ui.end_row();
+                        }
+                    });
+            });
+
+        egui::CollapsingHeader::new("Latest corrections").show(ui, |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(200.)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for event in &data.events {
+                        ui.label(format!(
+                            "{:?} {:?} {}: {:.3}",
+                            event.tick, event.entity, event.component, event.correction
+                        ));
+                    }
+                });
+        });
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui_mut(&self, ui: &mut egui::Ui, mut data: Mut<RollbackVisualizerData>) {
+        if ui.button("Reset").clicked() {
+            let current_tick = data.current_tick;
+            *data = RollbackVisualizerData {
+                current_tick,
+                ..default()
+            };
+        }
+        self.render_ui(ui, &data);
+    }
+
+    fn has_gizmos(&self) -> bool {
+        true
+    }
+
+    fn draw_gizmos(&self, gizmos: &mut Gizmos, data: &RollbackVisualizerData) {
+        let predicted_color = Color::srgb(0.2, 1., 0.2);
+        let confirmed_color = Color::srgba(1., 0.2, 0.2, 0.6);
+        for (predicted, confirmed) in &data.ghosts {
+            gizmos.sphere(*predicted, Quat::IDENTITY, GIZMO_RADIUS, predicted_color);
+            gizmos.sphere(*confirmed, Quat::IDENTITY, GIZMO_RADIUS, confirmed_color);
+            gizmos.line(*predicted, *confirmed, confirmed_color);
+        }
+    }
+}
+
+#[cfg(feature = "enabled")]
+/// Corrections of the last [`TIMELINE_TICKS`] ticks, as bars as high as the correction
+fn timeline(ui: &mut egui::Ui, current_tick: Tick, events: &VecDeque<RollbackEvent>) {
+    let desired_size = egui::vec2(ui.available_width(), 32.);
+    let (rect, _) = ui.allocate_exact_size(desired_size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2., ui.visuals().extreme_bg_color);
+
+    let largest = events
+        .iter()
+        .map(|event| event.correction)
+        .fold(f32::EPSILON, f32::max);
+    let stroke = egui::Stroke::new(1., egui::Color32::RED);
+    for event in events {
+        let ticks_ago = current_tick - event.tick;
+        if !(0..TIMELINE_TICKS).contains(&ticks_ago) {
+            continue;
+        }
+        let x = rect.right() - rect.width() * ticks_ago as f32 / TIMELINE_TICKS as f32;
+        let height = rect.height() * (event.correction / largest).clamp(0.1, 1.);
+        painter.line_segment(
+            [
+                egui::pos2(x, rect.bottom()),
+                egui::pos2(x, rect.bottom() - height),
+            ],
+            stroke,
+        );
+    }
+    ui.weak(format!("Last {TIMELINE_TICKS} ticks"));
+}
+
+/// Runs before the prediction check consumes the predicted history, and does the same comparison
+/// for each tracked component
+fn find_mispredictions(world: &mut World) {
+    let tracked = world.resource::<TrackedComponents>().clone();
+    let mut query = world.query::<(EntityRef, &Predicted)>();
+    let mispredicted = query
+        .iter(world)
+        .filter_map(|(entity_ref, predicted)| {
+            let confirmed = world.get_entity(predicted.confirmed_entity?)?;
+            let tick = confirmed.get::<Confirmed>()?.tick;
+            Some((entity_ref, confirmed, tick))
+        })
+        .flat_map(|(entity_ref, confirmed, tick)| {
+            tracked
+                .0
+                .iter()
+                .enumerate()
+                .filter(move |(_, component)| {
+                    (component.mispredicted)(&entity_ref, &confirmed, tick)
+                })
+                .filter_map(move |(index, component)| {
+                    Some((entity_ref.id(), index, (component.snapshot)(&entity_ref)?))
+                })
+        })
+        .collect();
+
+    world.resource_mut::<PendingRollback>().mispredicted = mispredicted;
+}
+
+fn snapshot_before_rollback(world: &mut World) {
+    let mispredicted = std::mem::take(&mut world.resource_mut::<PendingRollback>().mispredicted);
+    let Some(tick) = world
+        .get_resource::<Rollback>()
+        .and_then(|rollback| rollback.get_rollback_tick())
+    else {
+        return;
+    };
+
+    world.resource_mut::<PendingRollback>().rollback = Some((tick, mispredicted));
+    world
+        .resource_mut::<RollbackVisualizerData>()
+        .total_rollbacks += 1;
+}
+
+fn measure_corrections(world: &mut World) {
+    let tracked = world.resource::<TrackedComponents>().clone();
+    let pending = world.resource_mut::<PendingRollback>().rollback.take();
+
+    let mut events = vec![];
+    if let Some((tick, values)) = pending {
+        for (entity, index, before) in values {
+            let Some(entity_ref) = world.get_entity(entity) else {
+                continue;
+            };
+            let component = &tracked.0[index];
+            let Some(correction) = (component.correction)(before.as_ref(), &entity_ref) else {
+                continue;
+            };
+            if correction > MIN_CORRECTION {
+                events.push(RollbackEvent {
+                    tick,
+                    entity,
+                    component: get_short_name(component.name),
+                    correction,
+                });
+            }
+        }
+    }
+
+    let mut query = world.query::<(&Predicted, &Transform)>();
+    let ghosts = query
+        .iter(world)
+        .filter_map(|(predicted, transform)| {
+            let confirmed = world.get::<Transform>(predicted.confirmed_entity?)?;
+            Some((transform.translation, confirmed.translation))
+        })
+        .collect();
+
+    let despawned: Vec<_> = world
+        .resource::<RollbackVisualizerData>()
+        .entities
+        .keys()
+        .copied()
+        .filter(|entity| world.get_entity(*entity).is_none())
+        .collect();
+
+    let current_tick = world.resource::<TickManager>().tick();
+    let mut data = world.resource_mut::<RollbackVisualizerData>();
+    data.current_tick = Some(current_tick);
+    data.ghosts = ghosts;
+    for entity in despawned {
+        data.entities.remove(&entity);
+    }
+    for event in events {
+        data.push(event);
+    }
+}
@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use bevy::{diagnostic::DiagnosticsStore, ecs::entity::Entities, prelude::*};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+use lightyear::prelude::{server::ConnectionManager, ClientId, Replicating};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "enabled")]
+use crate::widgets::history_row;
+use crate::{typed::TypedDebugModule, viewing::DebugModuleSystems, widgets::History};
+
+/// Longer than the client side modules, since every sample is sent to all clients
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+/// 30 seconds worth of samples
+const HISTORY_LEN: usize = 60;
+
+/// Health of the server: frame times, tick overruns, entities, clients and diagnostics
+pub struct ServerPerformance;
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct ServerPerformanceData {
+    /// Average frame time of each sample interval
+    pub frame_time_ms: History,
+    /// Longest frame of each sample interval
+    pub worst_frame_time_ms: History,
+    pub tick_duration_ms: f32,
+    /// Frames that took longer than a tick, in each sample interval
+    pub tick_overruns: History,
+    pub total_tick_overruns: u64,
+    pub entity_count: u32,
+    pub replicated_entity_count: u32,
+    pub clients: Vec<ClientStats>,
+    /// Every diagnostic registered on the server, e.g. by `FrameTimeDiagnosticsPlugin` or by the
+    /// game itself
+    pub diagnostics: Vec<DiagnosticSample>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ClientStats {
+    pub client_id: ClientId,
+    pub rtt_ms: Option<f32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DiagnosticSample {
+    pub path: String,
+    pub value: Option<f64>,
+    pub suffix: String,
+}
+
+impl Default for ServerPerformanceData {
+    fn default() -> Self {
+        Self {
+            frame_time_ms: History::new(HISTORY_LEN),
+            worst_frame_time_ms: History::new(HISTORY_LEN),
+            tick_duration_ms: 0.,
+            tick_overruns: History::new(HISTORY_LEN),
+            total_tick_overruns: 0,
+            entity_count: 0,
+            replicated_entity_count: 0,
+            clients: vec![],
+            diagnostics: vec![],
+        }
+    }
+}
+
+impl TypedDebugModule for ServerPerformance {
+    type Data = ServerPerformanceData;
+
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems) {
+        app.add_systems(Last, sample_server_performance.in_set(set));
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: &ServerPerformanceData) {
+        ui.label(format!("Tick duration: {:.1} ms", data.tick_duration_ms));
+        ui.label(format!("Tick overruns: {}", data.total_tick_overruns));
+        ui.label(format!(
+            "Entities: {} ({} replicated)",
+            data.entity_count, data.replicated_entity_count
+        ));
+
+        ui.separator();
+        history_row(ui, "Frame time", &data.frame_time_ms, "ms");
+        history_row(ui, "Worst frame time", &data.worst_frame_time_ms, "ms");
+        history_row(ui, "Overruns", &data.tick_overruns, "");
+
+        ui.separator();
+        egui::CollapsingHeader::new(format!("Clients ({})", data.clients.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                for client in &data.clients {
+                    match client.rtt_ms {
+                        Some(rtt) => ui.label(format!("{:?}: {rtt:.1} ms", client.client_id)),
+                        None => ui.label(format!("{:?}: -", client.client_id)),
+                    };
+                }
+            });
+
+        egui::CollapsingHeader::new("Diagnostics").show(ui, |ui| {
+            egui::Grid::new("server_diagnostics")
+                .striped(true)
+                .show(ui, |ui| {
+                    for diagnostic in &data.diagnostics {
+                        ui.label(&diagnostic.path);
+                        match diagnostic.value {
+                            Some(value) => ui.label(format!("{value:.2}{}", diagnostic.suffix)),
+                            None => ui.label("-"),
+                        };
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+
+    fn render_text(&self, data: &ServerPerformanceData) -> Option<String> {
+        let latest = |history: &History| match history.latest() {
+            Some(latest) => format!("{latest:.1}"),
+            None => "-".to_string(),
+        };
+
+        let mut text = format!(
+            "Frame time: {} ms (worst {} ms), tick duration: {:.1} ms\n\
+             Tick overruns: {} (total {})\n\
+             Entities: {} ({} replicated)\n\
+             Clients: {}",
+            latest(&data.frame_time_ms),
+            latest(&data.worst_frame_time_ms),
+            data.tick_duration_ms,
+            latest(&data.tick_overruns),
+            data.total_tick_overruns,
+            data.entity_count,
+            data.replicated_entity_count,
+            data.clients.len(),
+        );
+        for client in &data.clients {
+            match client.rtt_ms {
+                Some(rtt) => text.push_str(&format!("\n  {:?}: {rtt:.1} ms", client.client_id)),
+                None => text.push_str(&format!("\n  {:?}: -", client.client_id)),
+            }
+        }
+        for diagnostic in &data.diagnostics {
+            match diagnostic.value {
+                Some(value) => text.push_str(&format!(
+                    "\n{}: {value:.2}{}",
+                    diagnostic.path, diagnostic.suffix
+                )),
+                None => text.push_str(&format!("\n{}: -", diagnostic.path)),
+            }
+        }
+        Some(text)
+    }
+}
+
+/// Frames seen since the last sample
+#[derive(Default)]
+struct FrameWindow {
+    elapsed: Duration,
+    frames: u32,
+    worst: Duration,
+    overruns: u32,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sample_server_performance(
+    time: Res<Time<Real>>,
+    fixed_time: Res<Time<Fixed>>,
+    entities: &Entities,
+    replicated: Query<(), With<Replicating>>,
+    connection_manager: Res<ConnectionManager>,
+    diagnostics: Option<Res<DiagnosticsStore>>,
+    mut window: Local<FrameWindow>,
+    mut data: ResMut<ServerPerformanceData>,
+) {
+    let frame_time = time.delta();
+    let tick_duration = fixed_time.timestep();
+    window.elapsed += frame_time;
+    window.frames += 1;
+    window.worst = window.worst.max(frame_time);
+    if frame_time > tick_duration {
+        window.overruns += 1;
+    }
+
+    // Only touch the data when sampling, every change gets replicated
+    if window.elapsed < SAMPLE_INTERVAL {
+        return;
+    }
+    let window = std::mem::take(&mut *window);
+
+    data.frame_time_ms
+        .push(window.elapsed.as_secs_f32() * 1000. / window.frames as f32);
+    data.worst_frame_time_ms
+        .push(window.worst.as_secs_f32() * 1000.);
+    data.tick_duration_ms = tick_duration.as_secs_f32() * 1000.;
+    data.tick_overruns.push(window.overruns as f32);
+    data.total_tick_overruns += window.overruns as u64;
+
+    data.entity_count = entities.len();
+    data.replicated_entity_count = replicated.iter().count() as u32;
+
+    data.clients = connection_manager
+        .connected_clients()
+        .map(|client_id| ClientStats {
+            client_id,
+            rtt_ms: connection_manager
+                .connection(client_id)
+                .ok()
+                .map(|connection| connection.rtt().as_secs_f32() * 1000.),
+        })
+        .collect();
+
+    data.diagnostics = diagnostics
+        .iter()
+        .flat_map(|diagnostics| diagnostics.iter())
+        .map(|diagnostic| DiagnosticSample {
+            path: diagnostic.path().to_string(),
+            value: diagnostic.smoothed(),
+            suffix: diagnostic.suffix.to_string(),
+        })
+        .collect();
+}
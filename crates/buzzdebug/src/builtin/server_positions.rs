@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, time::common_conditions::on_real_timer};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+use lightyear::prelude::Replicating;
+use serde::{Deserialize, Serialize};
+
+use crate::{typed::TypedDebugModule, viewing::DebugModuleSystems};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+/// Of the marker drawn at each position
+const GIZMO_RADIUS: f32 = 0.3;
+const GIZMO_COLOR: Color = Color::srgb(1., 0.8, 0.2);
+
+/// Positions of the replicated entities on the server, drawn as gizmos on the client to compare
+/// them with where the client shows those entities
+pub struct ServerPositions;
+
+#[derive(Resource, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ServerPositionsData {
+    pub positions: Vec<ServerPosition>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerPosition {
+    /// Server entity, which is not the same as the client one
+    pub entity: Entity,
+    pub position: Vec3,
+}
+
+impl TypedDebugModule for ServerPositions {
+    type Data = ServerPositionsData;
+
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems) {
+        app.add_systems(
+            Last,
+            sample_server_positions
+                .run_if(on_real_timer(SAMPLE_INTERVAL))
+                .in_set(set),
+        );
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: &ServerPositionsData) {
+        ui.label(format!("Replicated entities: {}", data.positions.len()));
+        egui::ScrollArea::vertical()
+            .max_height(200.)
+            .show(ui, |ui| {
+                egui::Grid::new("server_positions")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for position in &data.positions {
+                            ui.label(format!("{:?}", position.entity));
+                            ui.label(format!("{:.2}", position.position));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    fn has_gizmos(&self) -> bool {
+        true
+    }
+
+    fn draw_gizmos(&self, gizmos: &mut Gizmos, data: &ServerPositionsData) {
+        for position in &data.positions {
+            gizmos.sphere(position.position, Quat::IDENTITY, GIZMO_RADIUS, GIZMO_COLOR);
+        }
+    }
+}
+
+fn sample_server_positions(
+    replicated: Query<(Entity, &Transform), With<Replicating>>,
+    mut data: ResMut<ServerPositionsData>,
+) {
+    data.positions = replicated
+        .iter()
+        .map(|(entity, transform)| ServerPosition {
+            entity,
+            position: transform.translation,
+        })
+        .collect();
+    data.positions.sort_by_key(|position| position.entity);
+}
@@ -0,0 +1,189 @@
+use bevy::{app::RunFixedMainLoop, prelude::*};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+use lightyear::prelude::TimeManager;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "enabled")]
+use crate::commands::DebugCommands;
+use crate::{
+    commands::{register_debug_command, DebugCommandEvent},
+    typed::TypedDebugModule,
+    viewing::DebugModuleSystems,
+};
+
+/// Ticks stepped by a single command
+const MAX_STEP_TICKS: u32 = 600;
+const MIN_TIME_SCALE: f32 = 0.05;
+const MAX_TIME_SCALE: f32 = 4.;
+
+/// Pauses, slows down, speeds up and single-steps the fixed-tick simulation of the server. The
+/// state is replicated, and clients apply it to their own simulation so that they stay in sync
+/// with the server.
+pub struct TimeControl;
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimeControlData {
+    pub paused: bool,
+    /// Relative speed of the simulation
+    pub time_scale: f32,
+    /// Total ticks stepped while paused. Each app runs the ones it hasn't run yet, so that steps
+    /// merged into a single update still all run.
+    pub stepped_ticks: u64,
+}
+
+impl Default for TimeControlData {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            time_scale: 1.,
+            stepped_ticks: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TimeControlCommand {
+    SetPaused(bool),
+    SetTimeScale(f32),
+    /// Runs this many ticks, only while paused
+    Step(u32),
+}
+
+impl TypedDebugModule for TimeControl {
+    type Data = TimeControlData;
+
+    fn add_protocol(&self, app: &mut App) {
+        register_debug_command::<TimeControlCommand>(app);
+    }
+
+    /// Runs outside of `set`: the simulation must stay paused while nobody is viewing the module
+    fn add_update_systems(&self, app: &mut App, _: DebugModuleSystems) {
+        app.add_systems(PreUpdate, handle_time_control_commands);
+    }
+
+    /// Applies the state
+    fn add_server_systems(&self, app: &mut App) {
+        app.add_systems(RunFixedMainLoop, apply_time_control);
+    }
+
+    /// Applies the replicated state, to stay in sync with the server
+    fn add_client_systems(&self, app: &mut App) {
+        app.add_systems(RunFixedMainLoop, apply_time_control);
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: &TimeControlData) {
+        if data.paused {
+            ui.colored_label(egui::Color32::YELLOW, "Paused");
+        } else {
+            ui.label("Running");
+        }
+        ui.label(format!("Time scale: {:.2}", data.time_scale));
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui_with_commands(
+        &self,
+        ui: &mut egui::Ui,
+        data: &TimeControlData,
+        commands: &mut DebugCommands,
+    ) {
+        self.render_ui(ui, data);
+
+        ui.horizontal(|ui| {
+            let label = if data.paused { "Resume" } else { "Pause" };
+            if ui.button(label).clicked() {
+                commands.send(TimeControlCommand::SetPaused(!data.paused));
+            }
+            if ui.button("Normal speed").clicked() {
+                commands.send(TimeControlCommand::SetTimeScale(1.));
+            }
+        });
+
+        let mut time_scale = data.time_scale;
+        let response = ui.add(
+            egui::Slider::new(&mut time_scale, MIN_TIME_SCALE..=MAX_TIME_SCALE)
+                .logarithmic(true)
+                .text("Time scale"),
+        );
+        // Only once the slider is released, the server doesn't need every intermediate value
+        if response.drag_stopped() || (response.changed() && !response.dragged()) {
+            commands.send(TimeControlCommand::SetTimeScale(time_scale));
+        }
+
+        ui.add_enabled_ui(data.paused, |ui| {
+            ui.horizontal(|ui| {
+                let id = ui.make_persistent_id("time_control_step");
+                let mut ticks = ui.data_mut(|d| *d.get_temp_mut_or_insert_with(id, || 1u32));
+                ui.add(egui::DragValue::new(&mut ticks).clamp_range(1..=MAX_STEP_TICKS));
+                if ui.button("Step ticks").clicked() {
+                    commands.send(TimeControlCommand::Step(ticks));
+                }
+                ui.data_mut(|d| d.insert_temp(id, ticks));
+            });
+        });
+    }
+}
+
+fn handle_time_control_commands(
+    mut commands: EventReader<DebugCommandEvent<TimeControlCommand>>,
+    mut data: ResMut<TimeControlData>,
+) {
+    for event in commands.read() {
+        match event.command {
+            TimeControlCommand::SetPaused(paused) => data.paused = paused,
+            TimeControlCommand::SetTimeScale(time_scale) => {
+                data.time_scale = time_scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+            }
+            TimeControlCommand::Step(ticks) if data.paused => {
+                data.stepped_ticks += u64::from(ticks.min(MAX_STEP_TICKS));
+            }
+            TimeControlCommand::Step(_) => {
+                warn!(
+                    "Ignoring time step from {:?}, time is not paused",
+                    event.client_id
+                );
+            }
+        }
+    }
+}
+
+/// `applied_ticks` is the value of `stepped_ticks` the stepping is caught up to
+fn apply_time_control(world: &mut World, mut applied_ticks: Local<Option<u64>>) {
+    let data = world.resource::<TimeControlData>().clone();
+
+    let mut virtual_time = world.resource_mut::<Time<Virtual>>();
+    if data.paused && !virtual_time.is_paused() {
+        virtual_time.pause();
+    } else if !data.paused && virtual_time.is_paused() {
+        virtual_time.unpause();
+    }
+    // Lightyear adjusts the relative speed of the client to keep it in sync with the server, on
+    // top of this one
+    if world.is_resource_changed::<TimeControlData>() {
+        if let Some(mut time_manager) = world.get_resource_mut::<TimeManager>() {
+            time_manager.set_base_relative_speed(data.time_scale);
+        }
+    }
+
+    // Steps taken before this app saw the data, e.g. before a client joined, are not run
+    let applied = applied_ticks.replace(data.stepped_ticks);
+    let pending = applied.map_or(0, |applied| data.stepped_ticks.saturating_sub(applied));
+    if pending > 0 {
+        step_fixed_ticks(world, pending);
+    }
+}
+
+/// Runs [`FixedMain`] `ticks` times, the way [`RunFixedMainLoop`] does while time is not paused
+fn step_fixed_ticks(world: &mut World, ticks: u64) {
+    let timestep = world.resource::<Time<Fixed>>().timestep();
+    for _ in 0..ticks {
+        let mut fixed_time = world.resource_mut::<Time<Fixed>>();
+        fixed_time.advance_by(timestep);
+        let fixed_time = fixed_time.as_generic();
+        *world.resource_mut::<Time>() = fixed_time;
+        world.run_schedule(FixedMain);
+    }
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
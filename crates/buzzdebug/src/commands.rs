@@ -0,0 +1,126 @@
+//! Commands sent from the debug UI of a client to the server, for remote modules that need to
+//! control something on the server (gravity, spawn rates, ...).
+//!
+//! A module registers its command types with [`register_debug_command`] in its `add_protocol`,
+//! sends them from `render_ui_with_commands` through [`DebugCommands`], and handles them on the
+//! server by reading [`DebugCommandEvent`]s in its update systems. Only commands from clients
+//! allowed by the server's [`DebugAuthorization`] get through.
+
+use std::net::IpAddr;
+
+use bevy::{prelude::*, utils::HashSet};
+use lightyear::prelude::{
+    client,
+    server::{self, ServerConnections},
+    *,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use zusammen_app::client_ip;
+
+/// Channel for debug commands. Unlike remote data, every command matters and they must be
+/// applied in order.
+#[derive(Channel)]
+pub struct DebugCommandChannel;
+
+pub trait DebugCommand: Serialize + DeserializeOwned + Clone + Send + Sync + 'static {}
+
+impl<T: Serialize + DeserializeOwned + Clone + Send + Sync + 'static> DebugCommand for T {}
+
+/// Which clients are allowed to send debug commands to the server
+#[derive(Resource, Clone, Debug, Default)]
+pub enum DebugAuthorization {
+    Everyone,
+    /// Clients on the same machine as the server, including the client of Host mode
+    #[default]
+    Local,
+    Addresses(HashSet<IpAddr>),
+    Nobody,
+}
+
+impl DebugAuthorization {
+    pub fn allows(&self, ip: Option<IpAddr>) -> bool {
+        match self {
+            DebugAuthorization::Everyone => true,
+            DebugAuthorization::Local => ip.is_some_and(|ip| ip.is_loopback()),
+            DebugAuthorization::Addresses(addresses) => {
+                ip.is_some_and(|ip| addresses.contains(&ip))
+            }
+            DebugAuthorization::Nobody => false,
+        }
+    }
+}
+
+/// A command received on the server from an authorized client
+#[derive(Event, Clone, Debug)]
+pub struct DebugCommandEvent<C> {
+    pub client_id: ClientId,
+    pub command: C,
+}
+
+/// Functions adding the system that turns each registered command into [`DebugCommandEvent`]s,
+/// applied on the server
+#[derive(Resource, Default)]
+pub(crate) struct DebugCommandForwarders(pub Vec<fn(&mut App)>);
+
+/// Registers `C` as a command that clients can send to the server. Meant to be called from
+/// [`RemoteDebugModule::add_protocol`](crate::plugin::RemoteDebugModule::add_protocol).
+pub fn register_debug_command<C: DebugCommand>(app: &mut App) {
+    app.register_message::<C>(ChannelDirection::ClientToServer);
+    app.add_event::<DebugCommandEvent<C>>();
+    app.world_mut()
+        .get_resource_or_insert_with(DebugCommandForwarders::default)
+        .0
+        .push(forward_commands::<C>);
+}
+
+fn forward_commands<C: DebugCommand>(app: &mut App) {
+    app.add_systems(PreUpdate, forward_authorized_commands::<C>);
+}
+
+fn forward_authorized_commands<C: DebugCommand>(
+    mut messages: EventReader<server::MessageEvent<C>>,
+    mut commands: EventWriter<DebugCommandEvent<C>>,
+    authorization: Res<DebugAuthorization>,
+    connections: Res<ServerConnections>,
+) {
+    for message in messages.read() {
+        let client_id = *message.context();
+        let ip = client_ip(&connections, client_id);
+        if !authorization.allows(ip) {
+            warn!(
+                "Ignoring {} from unauthorized client {client_id:?} ({ip:?})",
+                std::any::type_name::<C>()
+            );
+            continue;
+        }
+        commands.send(DebugCommandEvent {
+            client_id,
+            command: message.message().clone(),
+        });
+    }
+}
+
+/// Commands queued while rendering the debug UI, sent once it's done
+#[derive(Default)]
+pub struct DebugCommands {
+    queue: Vec<Box<dyn FnOnce(&mut client::ConnectionManager) + Send + Sync>>,
+}
+
+impl DebugCommands {
+    pub fn send<C: DebugCommand>(&mut self, command: C) {
+        self.queue.push(Box::new(move |connection| {
+            if let Err(e) = connection.send_message::<DebugCommandChannel, C>(&command) {
+                error!(
+                    "Could not send {} to the server: {e:?}",
+                    std::any::type_name::<C>()
+                );
+            }
+        }));
+    }
+
+    pub(crate) fn apply(self, connection: &mut client::ConnectionManager) {
+        for send in self.queue {
+            send(connection);
+        }
+    }
+}
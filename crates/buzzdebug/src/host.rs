@@ -0,0 +1,225 @@
+//! Shortcut for the clients of Host mode, whose server runs in another app of the same process.
+//! The server and client apps are built from the same [`DebugZusammenPlugin`], so they share a
+//! [`HostDataLink`] through which the server hands over the latest copy of each remote module's
+//! data every frame it changes. Once connected, the clients on the link tell the server their
+//! [`ClientId`], and while the link is active the server leaves them out of the replication of the
+//! data it hands over, so that it's never serialized for them. Several clients can share the
+//! link, e.g. in tests, each one keeps track of the copies it already applied.
+//!
+//! The data of modules that can't be copied (see [`RemoteDebugModule::clone_data`]) is still
+//! replicated to every client.
+//!
+//! Clients of a different process never see a server on the link, and only use replication.
+//!
+//! [`DebugZusammenPlugin`]: crate::plugin::DebugZusammenPlugin
+//! [`RemoteDebugModule::clone_data`]: crate::plugin::RemoteDebugModule::clone_data
+
+use std::{
+    any::TypeId,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use bevy::{
+    ecs::component::ComponentId,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use lightyear::prelude::{
+    client::{ConnectEvent, DisconnectEvent},
+    ClientId,
+};
+
+use super::plugin::{DebugMode, DebugModules};
+
+type SharedData = Arc<dyn std::any::Any + Send + Sync>;
+
+/// Shared by the server and client apps built from the same plugin
+#[derive(Clone, Default)]
+pub(crate) struct HostDataLink(Arc<Mutex<HostData>>);
+
+#[derive(Default)]
+struct HostData {
+    /// Client apps built from the plugin
+    clients: usize,
+    /// Ids of the connected client apps built from the plugin
+    client_ids: HashSet<ClientId>,
+    /// A server app built from the plugin is running
+    server: bool,
+    /// Latest copy of the data of each module and its version, by module path
+    latest: HashMap<String, (u64, SharedData)>,
+    /// Version of the last copy handed over
+    version: u64,
+}
+
+impl HostDataLink {
+    fn lock(&self) -> MutexGuard<'_, HostData> {
+        // The data is only ever replaced, a panic while holding the lock can't leave it invalid
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// `shared` are the types of the data resources handed over through the link
+    pub fn add_server(&self, app: &mut App, shared: HashSet<TypeId>) {
+        self.lock().server = true;
+        app.insert_resource(HostDataWriter {
+            link: self.clone(),
+            shared,
+        });
+    }
+
+    pub fn add_client(&self, app: &mut App) {
+        self.lock().clients += 1;
+        app.insert_resource(HostDataReader {
+            link: self.clone(),
+            client_id: None,
+            latest: default(),
+        });
+    }
+}
+
+/// Server side of the link
+#[derive(Resource)]
+pub(crate) struct HostDataWriter {
+    link: HostDataLink,
+    shared: HashSet<TypeId>,
+}
+
+impl HostDataWriter {
+    /// Whether the data resource of type `data` reaches the clients on the link through it
+    pub fn shares(&self, data: TypeId) -> bool {
+        self.shared.contains(&data)
+    }
+
+    /// Connected clients that read the shared data from the link
+    pub fn linked_clients(&self) -> HashSet<ClientId> {
+        self.link.lock().client_ids.clone()
+    }
+}
+
+impl Drop for HostDataWriter {
+    fn drop(&mut self) {
+        let mut host_data = self.link.lock();
+        host_data.server = false;
+        host_data.latest.clear();
+    }
+}
+
+/// Client side of the link
+#[derive(Resource)]
+pub(crate) struct HostDataReader {
+    link: HostDataLink,
+    /// While connected
+    client_id: Option<ClientId>,
+    /// Latest data received through the link and its version, written again when replication
+    /// overwrites it
+    latest: HashMap<ComponentId, (u64, SharedData)>,
+}
+
+impl Drop for HostDataReader {
+    fn drop(&mut self) {
+        let mut host_data = self.link.lock();
+        host_data.clients -= 1;
+        if let Some(client_id) = self.client_id {
+            host_data.client_ids.remove(&client_id);
+        }
+    }
+}
+
+/// Tells the server which client the reader is, so that it stops replicating the shared data to it
+pub(crate) fn register_host_client(
+    mut connect_events: EventReader<ConnectEvent>,
+    mut disconnect_events: EventReader<DisconnectEvent>,
+    mut reader: ResMut<HostDataReader>,
+) {
+    let disconnected = disconnect_events.read().count() > 0;
+    let connected = connect_events.read().last().map(|event| event.client_id());
+    if !disconnected && connected.is_none() {
+        return;
+    }
+
+    let reader = &mut *reader;
+    let mut host_data = reader.link.lock();
+    if let Some(client_id) = reader.client_id.take() {
+        host_data.client_ids.remove(&client_id);
+    }
+    if let Some(client_id) = connected {
+        host_data.client_ids.insert(client_id);
+        reader.client_id = Some(client_id);
+    }
+}
+
+/// Hands the remote module data changed since the last run to the clients, if there are any
+pub(crate) fn write_host_data(world: &mut World) {
+    if world.resource::<HostDataWriter>().link.lock().clients == 0 {
+        return;
+    }
+
+    let debug_modules = world.resource::<DebugModules>();
+    let changed: Vec<_> = debug_modules
+        .modules
+        .iter()
+        .filter_map(|module| {
+            let DebugMode::Server(remote) = &module.mode else {
+                return None;
+            };
+            if !world.is_resource_changed_by_id(module.data) {
+                return None;
+            }
+            let data = remote.clone_data(world.get_resource_by_id(module.data)?)?;
+            Some((module.info.path.clone(), SharedData::from(data)))
+        })
+        .collect();
+
+    if changed.is_empty() {
+        return;
+    }
+    let mut host_data = world.resource::<HostDataWriter>().link.lock();
+    for (path, data) in changed {
+        host_data.version += 1;
+        let version = host_data.version;
+        host_data.latest.insert(path, (version, data));
+    }
+}
+
+/// Replaces the replicated data of the remote modules with the one handed over by the server
+pub(crate) fn read_host_data(world: &mut World) {
+    world.resource_scope(|world, mut reader: Mut<HostDataReader>| {
+        let reader = &mut *reader;
+        world.resource_scope(|world, debug_modules: Mut<DebugModules>| {
+            let host_data = reader.link.lock();
+            if !host_data.server {
+                reader.latest.clear();
+                return;
+            }
+
+            for module in &debug_modules.modules {
+                let DebugMode::Server(remote) = &module.mode else {
+                    continue;
+                };
+                let read_version = reader.latest.get(&module.data).map(|(version, _)| *version);
+                let newer = host_data
+                    .latest
+                    .get(&module.info.path)
+                    .filter(|(version, _)| Some(*version) > read_version);
+                // Changed since the last run means replication wrote older data
+                let replicated = world.is_resource_changed_by_id(module.data);
+                match newer {
+                    Some(latest) => {
+                        reader.latest.insert(module.data, latest.clone());
+                    }
+                    None if !replicated => continue,
+                    None => {}
+                }
+
+                let (Some((_, latest)), Some(data)) = (
+                    reader.latest.get(&module.data),
+                    world.get_resource_mut_by_id(module.data),
+                ) else {
+                    continue;
+                };
+                remote.set_data(data, latest.as_ref());
+            }
+        });
+    });
+}
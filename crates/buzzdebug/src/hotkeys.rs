@@ -0,0 +1,350 @@
+//! Named actions with keybindings, e.g. "Toggle collider view", registered by modules through
+//! their `actions` or by the game with
+//! [`DebugZusammenPlugin::with_action`](crate::plugin::DebugZusammenPlugin::with_action).
+//!
+//! Pressing the binding of an action sends a [`DebugActionEvent`] on the client, whether the
+//! debug window is shown or not. Systems reacting to it must not be in the module's
+//! [`DebugModuleSystems`](crate::viewing::DebugModuleSystems), which only run while the module is
+//! viewed; the [`debug_action`] run condition is meant for them. Bindings can be changed from the
+//! debug window, and are kept with its layout.
+
+#[cfg(feature = "enabled")]
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bevy::prelude::*;
+#[cfg(feature = "enabled")]
+use bevy::utils::HashMap;
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::{bevy_egui::EguiContext, egui};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "enabled")]
+use super::{plugin::DebugModules, ui::DebugMenuData};
+
+#[derive(Clone, Debug)]
+pub struct DebugAction {
+    /// Identifies the action in [`DebugActionEvent`]s and in the saved bindings
+    pub id: String,
+    /// Shown in the debug window
+    pub label: String,
+    pub default_binding: Option<KeyBinding>,
+}
+
+impl DebugAction {
+    pub fn new(id: impl Into<String>, label: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            label: label.into(),
+            default_binding: None,
+        }
+    }
+
+    pub fn with_default_binding(mut self, binding: impl Into<KeyBinding>) -> Self {
+        self.default_binding = Some(binding.into());
+        self
+    }
+}
+
+/// A key, with the modifiers that must be held along with it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct KeyBinding {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyBinding {
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    pub fn ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    pub fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+
+    #[cfg(feature = "enabled")]
+    /// The binding of `key` with the modifiers currently held
+    pub(crate) fn pressed_with(key: KeyCode, keys: &ButtonInput<KeyCode>) -> Self {
+        Self {
+            key,
+            ctrl: keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]),
+            shift: keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]),
+            alt: keys.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]),
+        }
+    }
+}
+
+impl From<KeyCode> for KeyBinding {
+    fn from(key: KeyCode) -> Self {
+        Self::new(key)
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, modifier) in [
+            (self.ctrl, "Ctrl"),
+            (self.shift, "Shift"),
+            (self.alt, "Alt"),
+        ] {
+            if held {
+                write!(f, "{modifier}+")?;
+            }
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+/// Sent on the client when the binding of an action is pressed
+#[derive(Event, Clone, Debug)]
+pub struct DebugActionEvent {
+    pub id: String,
+}
+
+/// Run condition, true on the frames the action `id` is triggered
+pub fn debug_action(id: &'static str) -> impl FnMut(EventReader<DebugActionEvent>) -> bool + Clone {
+    move |mut events: EventReader<DebugActionEvent>| {
+        events.read().filter(|event| event.id == id).count() > 0
+    }
+}
+
+#[cfg(feature = "enabled")]
+/// Binding of each action, `None` if it's unbound. `overrides` are the bindings changed from the
+/// debug window.
+pub(crate) fn effective_bindings<'a>(
+    actions: &'a [DebugAction],
+    overrides: &'a BTreeMap<String, Option<KeyBinding>>,
+) -> impl Iterator<Item = (&'a DebugAction, Option<KeyBinding>)> {
+    actions.iter().map(|action| {
+        let binding = overrides
+            .get(&action.id)
+            .copied()
+            .unwrap_or(action.default_binding);
+        (action, binding)
+    })
+}
+
+#[cfg(feature = "enabled")]
+/// Bindings used by more than one action or by the toggle key of the debug window, which is
+/// pressed without modifiers
+pub(crate) fn conflicting_bindings(
+    actions: &[DebugAction],
+    overrides: &BTreeMap<String, Option<KeyBinding>>,
+    toggle_key: KeyCode,
+) -> Vec<KeyBinding> {
+    let mut uses = HashMap::<KeyBinding, usize>::new();
+    *uses.entry(KeyBinding::new(toggle_key)).or_default() += 1;
+    for (_, binding) in effective_bindings(actions, overrides) {
+        if let Some(binding) = binding {
+            *uses.entry(binding).or_default() += 1;
+        }
+    }
+    uses.into_iter()
+        .filter_map(|(binding, uses)| (uses > 1).then_some(binding))
+        .collect()
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn trigger_debug_actions(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut contexts: Query<&mut EguiContext>,
+    debug_modules: Res<DebugModules>,
+    mut menu: ResMut<DebugMenuData>,
+    mut events: EventWriter<DebugActionEvent>,
+) {
+    let Some(&key) = keys.get_just_pressed().find(|key| !is_modifier(**key)) else {
+        return;
+    };
+
+    if let Some(id) = menu.rebinding.take() {
+        // Escape cancels the rebinding instead of binding it
+        if key != KeyCode::Escape {
+            let binding = KeyBinding::pressed_with(key, &keys);
+            menu.layout.bindings.insert(id, Some(binding));
+        }
+        return;
+    }
+
+    // Keys typed into the debug window, e.g. in the console, aren't hotkeys
+    if contexts
+        .iter_mut()
+        .any(|mut ctx| ctx.get_mut().wants_keyboard_input())
+    {
+        return;
+    }
+
+    let pressed = KeyBinding::pressed_with(key, &keys);
+    for (action, binding) in effective_bindings(&debug_modules.actions, &menu.layout.bindings) {
+        if binding == Some(pressed) {
+            events.send(DebugActionEvent {
+                id: action.id.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(feature = "enabled")]
+fn is_modifier(key: KeyCode) -> bool {
+    matches!(
+        key,
+        KeyCode::ControlLeft
+            | KeyCode::ControlRight
+            | KeyCode::ShiftLeft
+            | KeyCode::ShiftRight
+            | KeyCode::AltLeft
+            | KeyCode::AltRight
+            | KeyCode::SuperLeft
+            | KeyCode::SuperRight
+    )
+}
+
+#[cfg(feature = "enabled")]
+/// Rebinding panel of the debug window
+pub(crate) fn hotkeys_panel(
+    ui: &mut egui::Ui,
+    actions: &[DebugAction],
+    bindings: &mut BTreeMap<String, Option<KeyBinding>>,
+    rebinding: &mut Option<String>,
+    toggle_key: KeyCode,
+) {
+    let conflicts = conflicting_bindings(actions, bindings, toggle_key);
+    let mut changes = vec![];
+
+    egui::Grid::new("buzzdebug_hotkeys")
+        .striped(true)
+        .show(ui, |ui| {
+            for (action, binding) in effective_bindings(actions, bindings) {
+                ui.label(&action.label);
+
+                let text = if rebinding.as_ref() == Some(&action.id) {
+                    egui::RichText::new("Press a key...").italics()
+                } else {
+                    match binding {
+                        Some(binding) if conflicts.contains(&binding) => {
+                            egui::RichText::new(binding.to_string()).color(egui::Color32::RED)
+                        }
+                        Some(binding) => egui::RichText::new(binding.to_string()),
+                        None => egui::RichText::new("Unbound").weak(),
+                    }
+                };
+                let mut response = ui.button(text);
+                if binding.is_some_and(|binding| conflicts.contains(&binding)) {
+                    response = response.on_hover_text("Also bound to another action");
+                }
+                if response.clicked() {
+                    *rebinding = Some(action.id.clone());
+                }
+
+                if ui.small_button("Clear").clicked() {
+                    changes.push((action.id.clone(), Some(None)));
+                }
+                if ui.small_button("Default").clicked() {
+                    changes.push((action.id.clone(), None));
+                }
+                ui.end_row();
+            }
+        });
+
+    for (id, binding) in changes {
+        match binding {
+            Some(binding) => bindings.insert(id, binding),
+            None => bindings.remove(&id),
+        };
+    }
+}
+
+#[cfg(all(test, feature = "enabled"))]
+mod tests {
+    use super::*;
+
+    const TOGGLE_KEY: KeyCode = KeyCode::F12;
+
+    fn action(id: &str, binding: impl Into<KeyBinding>) -> DebugAction {
+        DebugAction::new(id, id).with_default_binding(binding)
+    }
+
+    fn conflicts(
+        actions: &[DebugAction],
+        overrides: &[(&str, Option<KeyBinding>)],
+    ) -> Vec<KeyBinding> {
+        let overrides = overrides
+            .iter()
+            .map(|(id, binding)| (id.to_string(), *binding))
+            .collect();
+        conflicting_bindings(actions, &overrides, TOGGLE_KEY)
+    }
+
+    #[test]
+    fn distinct_bindings_do_not_conflict() {
+        let actions = [
+            action("a", KeyCode::KeyA),
+            action("b", KeyCode::KeyB),
+            DebugAction::new("unbound", "Unbound"),
+        ];
+        assert!(conflicts(&actions, &[]).is_empty());
+    }
+
+    #[test]
+    fn same_binding_conflicts() {
+        let actions = [
+            action("a", KeyBinding::new(KeyCode::KeyA).ctrl()),
+            action("b", KeyBinding::new(KeyCode::KeyA).ctrl()),
+        ];
+        assert_eq!(
+            conflicts(&actions, &[]),
+            vec![KeyBinding::new(KeyCode::KeyA).ctrl()]
+        );
+    }
+
+    #[test]
+    fn modifiers_tell_bindings_apart() {
+        let actions = [
+            action("a", KeyCode::KeyA),
+            action("ctrl", KeyBinding::new(KeyCode::KeyA).ctrl()),
+            action("shift", KeyBinding::new(KeyCode::KeyA).shift()),
+            action("ctrl_shift", KeyBinding::new(KeyCode::KeyA).ctrl().shift()),
+            action("alt", KeyBinding::new(KeyCode::KeyA).alt()),
+        ];
+        assert!(conflicts(&actions, &[]).is_empty());
+    }
+
+    #[test]
+    fn toggle_key_conflicts_only_without_modifiers() {
+        let actions = [action("ctrl", KeyBinding::new(TOGGLE_KEY).ctrl())];
+        assert!(conflicts(&actions, &[]).is_empty());
+
+        let actions = [action("bare", TOGGLE_KEY)];
+        assert_eq!(conflicts(&actions, &[]), vec![KeyBinding::new(TOGGLE_KEY)]);
+    }
+
+    #[test]
+    fn overrides_replace_default_bindings() {
+        let actions = [action("a", KeyCode::KeyA), action("b", KeyCode::KeyA)];
+        assert!(conflicts(&actions, &[("b", None)]).is_empty());
+        assert!(conflicts(&actions, &[("b", Some(KeyCode::KeyB.into()))]).is_empty());
+
+        let actions = [action("a", KeyCode::KeyA), action("b", KeyCode::KeyB)];
+        assert_eq!(
+            conflicts(&actions, &[("b", Some(KeyCode::KeyA.into()))]),
+            vec![KeyBinding::new(KeyCode::KeyA)]
+        );
+    }
+}
@@ -1,3 +1,22 @@
+//! Debug UI for zusammen apps, see [`plugin::DebugZusammenPlugin`].
+//!
+//! Everything but the protocol is behind the `enabled` feature (on by default). Without it,
+//! [`plugin::DebugZusammenPlugin`] only registers the protocol items of its modules, so that
+//! builds without the debug UI stay compatible with servers and clients that have it. Modules
+//! written outside of this crate should put their `render_ui*` methods behind a feature of their
+//! own crate that enables `buzzdebug/enabled`.
+
 pub mod builtin;
+pub mod commands;
+#[cfg(feature = "enabled")]
+pub mod host;
+pub mod hotkeys;
+pub mod observer;
 pub mod plugin;
+pub mod recording;
+pub mod replication;
+pub mod snapshot;
+pub mod typed;
 pub mod ui;
+pub mod viewing;
+pub mod widgets;
@@ -0,0 +1,292 @@
+//! Debug observers: lightweight connections to a running server that receive the data of its
+//! remote modules without joining the game as a player, e.g. to look into a dedicated server.
+//!
+//! The server listens on a separate TCP port (see
+//! [`DebugZusammenPlugin::with_observers`](crate::plugin::DebugZusammenPlugin::with_observers))
+//! and streams [`RecordedFrame`]s of its remote modules, one RON line each, to
+//! [`DebugObserverPlugin`] apps. Only addresses allowed by the server's [`DebugAuthorization`] can
+//! observe it. While an observer is connected, every remote module is updated.
+
+use std::time::Duration;
+#[cfg(feature = "enabled")]
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    sync::Arc,
+    thread,
+};
+
+#[cfg(feature = "enabled")]
+use bevy::{prelude::*, scene::ron};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::bevy_egui::EguiPlugin;
+#[cfg(feature = "enabled")]
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+
+#[cfg(feature = "enabled")]
+use super::{
+    commands::DebugAuthorization,
+    plugin::DebugZusammenPlugin,
+    recording::RecordedFrame,
+    replication::{track_remote_data_updates, RemoteDataUpdates},
+    ui::{debug_menu_system, draw_debug_gizmos, toggle_debug_menu},
+    viewing::{DebugModuleViewers, EnabledDebugModules},
+};
+
+#[cfg(feature = "enabled")]
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+#[cfg(feature = "enabled")]
+/// Frames waiting to be sent to a slow observer, newer ones are dropped
+const MAX_QUEUED_FRAMES: usize = 8;
+#[cfg(feature = "enabled")]
+/// A write blocked for longer than this disconnects the observer
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+#[cfg(feature = "enabled")]
+/// An observer whose queue stays full for this long is dropped, so that it doesn't keep every
+/// remote module updating
+const STALLED_AFTER: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub struct ObserverConfig {
+    pub port: u16,
+    /// Time between frames sent to observers
+    pub interval: Duration,
+}
+
+impl ObserverConfig {
+    pub fn new(port: u16) -> Self {
+        Self {
+            port,
+            interval: Duration::from_millis(250),
+        }
+    }
+}
+
+#[cfg(feature = "enabled")]
+/// Listener for observers and the ones connected, on the server
+#[derive(Resource)]
+struct ObserverServer {
+    listener: TcpListener,
+    interval: Duration,
+    last_sent: Duration,
+    observers: Vec<Observer>,
+}
+
+#[cfg(feature = "enabled")]
+struct Observer {
+    address: SocketAddr,
+    /// Frames for the thread writing to the observer
+    frames: Sender<Arc<str>>,
+    /// Since when its queue has been full
+    full_since: Option<Duration>,
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn add_observer_server(app: &mut App, config: &ObserverConfig) {
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, config.port)).and_then(|listener| {
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    });
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(
+                "Could not listen for debug observers on port {}: {e}",
+                config.port
+            );
+            return;
+        }
+    };
+
+    app.insert_resource(ObserverServer {
+        listener,
+        interval: config.interval,
+        last_sent: Duration::ZERO,
+        observers: vec![],
+    });
+    app.add_systems(Last, serve_observers);
+}
+
+#[cfg(feature = "enabled")]
+impl ObserverServer {
+    fn accept(&mut self, authorization: &DebugAuthorization) {
+        loop {
+            let (stream, address) = match self.listener.accept() {
+                Ok(accepted) => accepted,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return,
+                Err(e) => {
+                    warn!("Could not accept a debug observer: {e}");
+                    return;
+                }
+            };
+            if !authorization.allows(Some(address.ip())) {
+                warn!("Refusing debug observer from unauthorized address {address}");
+                continue;
+            }
+            let blocking = stream
+                .set_nonblocking(false)
+                .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)));
+            if let Err(e) = blocking {
+                warn!("Could not set up debug observer {address}: {e}");
+                continue;
+            }
+
+            info!("Debug observer connected from {address}");
+            let (sender, receiver) = crossbeam_channel::bounded(MAX_QUEUED_FRAMES);
+            thread::spawn(move || send_frames(stream, address, receiver));
+            self.observers.push(Observer {
+                address,
+                frames: sender,
+                full_since: None,
+            });
+        }
+    }
+}
+
+#[cfg(feature = "enabled")]
+fn send_frames(mut stream: TcpStream, address: SocketAddr, frames: Receiver<Arc<str>>) {
+    for frame in frames {
+        if let Err(e) = writeln!(stream, "{frame}") {
+            info!("Debug observer {address} disconnected: {e}");
+            return;
+        }
+    }
+}
+
+#[cfg(feature = "enabled")]
+fn serve_observers(world: &mut World) {
+    let now = world.resource::<Time<Real>>().elapsed();
+    world.resource_scope(|world, mut server: Mut<ObserverServer>| {
+        server.accept(world.resource::<DebugAuthorization>());
+
+        if !server.observers.is_empty() && now.saturating_sub(server.last_sent) >= server.interval {
+            server.last_sent = now;
+            match ron::to_string(&RecordedFrame::capture(world, now)) {
+                Ok(frame) => {
+                    let frame: Arc<str> = frame.into();
+                    // The writing thread is gone once its observer disconnects or a write times
+                    // out. Dropping the sender of a stalled one also ends it, once it's drained.
+                    server.observers.retain_mut(|observer| {
+                        match observer.frames.try_send(frame.clone()) {
+                            Ok(()) => {
+                                observer.full_since = None;
+                                true
+                            }
+                            Err(TrySendError::Full(_)) => {
+                                let full_since = *observer.full_since.get_or_insert(now);
+                                let stalled = now.saturating_sub(full_since) >= STALLED_AFTER;
+                                if stalled {
+                                    warn!("Dropping stalled debug observer {}", observer.address);
+                                }
+                                !stalled
+                            }
+                            Err(TrySendError::Disconnected(_)) => false,
+                        }
+                    });
+                }
+                Err(e) => error!("Could not serialize debug frame for observers: {e}"),
+            }
+        }
+
+        let observers = server.observers.len();
+        if world.resource::<DebugModuleViewers>().observers != observers {
+            let mut viewers = world.resource_mut::<DebugModuleViewers>();
+            viewers.observers = observers;
+            let enabled = viewers.enabled();
+            world
+                .resource_mut::<EnabledDebugModules>()
+                .set_if_neq(enabled);
+        }
+    });
+}
+
+#[cfg(feature = "enabled")]
+/// Standalone app showing the remote modules of the server it observes. Needs the same remote
+/// modules as the server, e.g.
+/// `App::new().add_plugins((DefaultPlugins, DebugObserverPlugin { modules, server_addr }))`.
+pub struct DebugObserverPlugin {
+    pub modules: DebugZusammenPlugin,
+    /// Address of the server's observer port
+    pub server_addr: SocketAddr,
+}
+
+#[cfg(feature = "enabled")]
+/// Frames received from the server by the connection thread
+#[derive(Resource)]
+struct ObservedFrames(Receiver<RecordedFrame>);
+
+#[cfg(feature = "enabled")]
+impl Plugin for DebugObserverPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        // Local modules have nothing to show without a game client
+        let modules = DebugZusammenPlugin {
+            locals: vec![],
+            ..self.modules.clone()
+        };
+        modules.add_menu(app);
+        let debug_modules = modules.insert_modules(app, true);
+        app.insert_resource(debug_modules);
+        app.init_resource::<RemoteDataUpdates>();
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let server_addr = self.server_addr;
+        thread::spawn(move || receive_frames(server_addr, sender));
+        app.insert_resource(ObservedFrames(receiver));
+
+        app.add_systems(
+            Update,
+            (
+                toggle_debug_menu.run_if(resource_exists::<ButtonInput<KeyCode>>),
+                apply_observed_frames,
+                track_remote_data_updates,
+                debug_menu_system,
+                draw_debug_gizmos.run_if(resource_exists::<GizmoConfigStore>),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[cfg(feature = "enabled")]
+fn receive_frames(server_addr: SocketAddr, frames: Sender<RecordedFrame>) {
+    loop {
+        match TcpStream::connect(server_addr) {
+            Ok(stream) => {
+                info!("Observing {server_addr}");
+                for line in BufReader::new(stream).lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(e) => {
+                            warn!("Lost connection to {server_addr}: {e}");
+                            break;
+                        }
+                    };
+                    match ron::from_str(&line) {
+                        Ok(frame) => {
+                            if frames.send(frame).is_err() {
+                                // The app is gone
+                                return;
+                            }
+                        }
+                        Err(e) => warn!("Invalid debug frame from {server_addr}: {e}"),
+                    }
+                }
+            }
+            Err(e) => warn!("Could not connect to {server_addr}: {e}"),
+        }
+        thread::sleep(RECONNECT_INTERVAL);
+    }
+}
+
+#[cfg(feature = "enabled")]
+fn apply_observed_frames(world: &mut World) {
+    // Only the latest data matters
+    let latest = world.resource::<ObservedFrames>().0.try_iter().last();
+    if let Some(frame) = latest {
+        frame.load(world);
+    }
+}
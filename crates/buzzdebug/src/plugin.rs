@@ -3,41 +3,264 @@
 //! 2. System(s) for updating data resource (maybe placed together in plugin)
 //! 3. Rendering function showing UI based on data resource i.e. `fn(&mut egui::Ui, &DataRes)`
 //!
-//! Additionally, if the module is remote, items 1. and 2. would run in the server, and the data
-//! resource would need to be replicated onto client. The UI function would obviously run in
-//! client
+//! Additionally, if the module is remote, items 1. and 2. run in the server, and the data
+//! resource is replicated onto the client (see [`crate::replication`]). The UI function obviously
+//! runs in the client
+//!
+//! The traits here are type-erased so that modules can be stored together. Modules are easier to
+//! write through [`crate::typed::TypedDebugModule`], which implements both.
 
-use std::sync::Arc;
+use std::{any::Any, path::PathBuf, sync::Arc};
 
-use bevy::{ecs::component::ComponentId, prelude::*, ptr::Ptr};
+use bevy::{
+    ecs::{change_detection::MutUntyped, component::ComponentId, system::SystemState},
+    prelude::*,
+    ptr::Ptr,
+};
+#[cfg(feature = "enabled")]
+use bevy::{time::common_conditions::on_real_timer, utils::HashSet};
+#[cfg(feature = "enabled")]
 use bevy_inspector_egui::egui;
+use lightyear::prelude::*;
 use zusammen_plugin::ZusammenPlugin;
 
-use super::ui::{debug_menu_system, DebugMenuData};
+#[cfg(feature = "enabled")]
+use super::{
+    commands::DebugCommands,
+    host::{read_host_data, register_host_client, write_host_data, HostDataLink},
+    hotkeys::{conflicting_bindings, trigger_debug_actions, DebugActionEvent},
+    observer::add_observer_server,
+    recording::{record_debug_frame, recording, DebugRecorder},
+    replication::{track_remote_data_updates, update_remote_data_targets, RemoteDataUpdates},
+    snapshot::log_debug_snapshot,
+    ui::{
+        debug_menu_system, draw_debug_gizmos, save_debug_layout, toggle_debug_menu, DebugLayout,
+        DebugMenuData, SAVE_LAYOUT_INTERVAL,
+    },
+    viewing::{
+        configure_module_systems, data_name, receive_viewed_modules, send_viewed_modules,
+        DebugModuleViewers, EnabledDebugModules,
+    },
+};
+use super::{
+    commands::{DebugAuthorization, DebugCommandChannel, DebugCommandForwarders},
+    hotkeys::DebugAction,
+    observer::ObserverConfig,
+    recording::RecordingConfig,
+    replication::{DebugChannel, RemoteDataReplicators},
+    snapshot::SnapshotLogConfig,
+    ui::{DEFAULT_LAYOUT_PATH, DEFAULT_TOGGLE_KEY},
+    viewing::{DebugModuleSystems, ViewedModules},
+};
 
 #[derive(Default)]
 pub struct ServerMarker;
 #[derive(Default)]
 pub struct ClientMarker;
 
+/// Gizmos the modules draw their overlays with, applied to the world once all of them drew
+pub type DebugGizmos = SystemState<Gizmos<'static, 'static>>;
+
 pub trait LocalDebugModule: Send + Sync {
+    /// Protocol items the module needs, e.g. commands sent to the server (see [`crate::commands`])
+    fn add_protocol(&self, _: &mut App) {}
     fn insert_data(&self, app: &mut App) -> ComponentId;
-    fn add_update_systems(&self, app: &mut App);
+    /// Inserts the data resource in the standalone apps that show recorded or observed data
+    /// (see [`crate::recording`] and [`crate::observer`]), which have no game or networking
+    /// resources for it to be made from
+    fn insert_standalone_data(&self, app: &mut App) -> ComponentId {
+        self.insert_data(app)
+    }
+    /// The systems have to be added to `set`, so that they only run while the module is viewed
+    /// (see [`LocalDebugModule::sample_always`])
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems);
+    /// Systems the module needs on the server, whether it's viewed or not, e.g. to answer what
+    /// the module asks for from the client. Not added without the `enabled` feature.
+    fn add_server_systems(&self, _: &mut App) {}
+    /// Systems the module needs on the client, whether it's viewed or not, e.g. to apply what
+    /// the server decided. Not added without the `enabled` feature.
+    fn add_client_systems(&self, _: &mut App) {}
+    /// Whether the update systems keep running while the module isn't viewed, as long as it's
+    /// enabled. For cheap sampling whose history should be there when the module is opened.
+    fn sample_always(&self) -> bool {
+        false
+    }
+    #[cfg(feature = "enabled")]
     fn render_ui(&self, ui: &mut egui::Ui, data: Ptr);
+    /// Plain text version of the UI, for snapshots (see [`crate::snapshot`])
+    fn render_text(&self, _: Ptr) -> Option<String> {
+        None
+    }
+    /// Serialized data resource for recordings (see [`crate::recording`]), `None` if the module
+    /// can't be recorded
+    fn save_data(&self, _: Ptr) -> Option<String> {
+        None
+    }
+    /// Replaces the data resource with one serialized by [`LocalDebugModule::save_data`]
+    fn load_data(&self, _: MutUntyped, _: &str) -> Result<(), String> {
+        Err("the module can't be recorded".to_string())
+    }
+    /// Same as [`LocalDebugModule::render_ui`], but allowed to edit the data resource. Only
+    /// access it mutably (which marks it as changed) when something was actually edited.
+    #[cfg(feature = "enabled")]
+    fn render_ui_mut(&self, ui: &mut egui::Ui, data: MutUntyped) {
+        self.render_ui(ui, data.as_ref());
+    }
+    /// Whether the module draws world-space overlays with [`LocalDebugModule::draw_gizmos`].
+    /// They can then be toggled from the debug window.
+    fn has_gizmos(&self) -> bool {
+        false
+    }
+    /// Draws the module's overlays from its data resource in `world`, every frame while they are
+    /// toggled on
+    fn draw_gizmos(&self, _: &mut World, _: &mut DebugGizmos) {}
+    /// Actions that can be bound to keys (see [`crate::hotkeys`])
+    fn actions(&self) -> Vec<DebugAction> {
+        vec![]
+    }
 }
 
 pub trait RemoteDebugModule: Send + Sync {
-    #[allow(unused_variables)]
-    fn add_protocol(&self, app: &mut App) {}
+    /// Has to register the data resource with
+    /// [`register_remote_data`](crate::replication::register_remote_data), along with any other
+    /// protocol items the module needs
+    fn add_protocol(&self, app: &mut App);
     fn insert_data(&self, app: &mut App) -> ComponentId;
-    fn add_update_systems(&self, app: &mut App);
+    /// Inserts the data resource in the standalone apps that show recorded or observed data
+    /// (see [`crate::recording`] and [`crate::observer`]), which have no game or networking
+    /// resources for it to be made from
+    fn insert_standalone_data(&self, app: &mut App) -> ComponentId {
+        self.insert_data(app)
+    }
+    /// The systems have to be added to `set`, so that they only run while some client is viewing
+    /// the module (see [`RemoteDebugModule::sample_always`])
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems);
+    /// See [`LocalDebugModule::add_server_systems`]
+    fn add_server_systems(&self, _: &mut App) {}
+    /// See [`LocalDebugModule::add_client_systems`]
+    fn add_client_systems(&self, _: &mut App) {}
+    /// Whether the update systems keep running while the module isn't viewed, as long as it's
+    /// enabled. For cheap sampling whose history should be there when the module is opened.
+    fn sample_always(&self) -> bool {
+        false
+    }
+    #[cfg(feature = "enabled")]
     fn render_ui(&self, ui: &mut egui::Ui, data: Ptr);
+    /// Plain text version of the UI, for snapshots taken on servers without a UI (see
+    /// [`crate::snapshot`])
+    fn render_text(&self, _: Ptr) -> Option<String> {
+        None
+    }
+    /// Serialized data resource for recordings (see [`crate::recording`]), `None` if the module
+    /// can't be recorded
+    fn save_data(&self, _: Ptr) -> Option<String> {
+        None
+    }
+    /// Replaces the data resource with one serialized by [`RemoteDebugModule::save_data`]
+    fn load_data(&self, _: MutUntyped, _: &str) -> Result<(), String> {
+        Err("the module can't be recorded".to_string())
+    }
+    /// Same as [`RemoteDebugModule::render_ui`], with a way to send commands to the server (see
+    /// [`crate::commands`])
+    #[cfg(feature = "enabled")]
+    fn render_ui_with_commands(&self, ui: &mut egui::Ui, data: Ptr, _: &mut DebugCommands) {
+        self.render_ui(ui, data);
+    }
+    /// Whether the module draws world-space overlays with [`RemoteDebugModule::draw_gizmos`].
+    /// They can then be toggled from the debug window.
+    fn has_gizmos(&self) -> bool {
+        false
+    }
+    /// Draws the module's overlays on the client from the replicated data resource in `world`,
+    /// every frame while they are toggled on
+    fn draw_gizmos(&self, _: &mut World, _: &mut DebugGizmos) {}
+    /// Copy of the data resource handed to the client of Host mode without serializing it (see
+    /// [`crate::host`]), `None` to only use replication
+    fn clone_data(&self, _: Ptr) -> Option<Box<dyn Any + Send + Sync>> {
+        None
+    }
+    /// Replaces the data resource with a copy made by [`RemoteDebugModule::clone_data`]
+    fn set_data(&self, _: MutUntyped, _: &dyn Any) {}
+    /// Actions that can be bound to keys on the client (see [`crate::hotkeys`])
+    fn actions(&self) -> Vec<DebugAction> {
+        vec![]
+    }
 }
 
+/// Without the `enabled` feature, only registers the protocol items of the debug window and of
+/// its modules, so that apps built with and without it can still connect to each other.
+///
+/// Only the registered messages and resources (e.g. remote data, and commands sent to the server)
+/// are part of the protocol fingerprint, peers can have different local modules, and show the
+/// modules under different paths.
 #[derive(Resource, Clone)]
 pub struct DebugZusammenPlugin {
-    pub locals: Vec<(String, Arc<dyn LocalDebugModule>)>,
-    pub remote: Vec<(String, Arc<dyn RemoteDebugModule>)>,
+    pub locals: Vec<(DebugModuleInfo, Arc<dyn LocalDebugModule>)>,
+    pub remote: Vec<(DebugModuleInfo, Arc<dyn RemoteDebugModule>)>,
+    /// Which clients the server accepts debug commands from and sends remote data to
+    pub authorization: DebugAuthorization,
+    /// Shows and hides the debug window
+    pub toggle_key: KeyCode,
+    /// Where the layout of the debug UI is kept between runs, `None` to not keep it
+    pub layout_path: Option<PathBuf>,
+    /// Periodic snapshots of the remote modules on the server, mainly for dedicated servers
+    pub snapshot_log: Option<SnapshotLogConfig>,
+    /// Recordings of the module data made from the debug window
+    pub recordings: RecordingConfig,
+    /// Port on which the server accepts debug observers, if any
+    pub observers: Option<ObserverConfig>,
+    /// Actions that can be bound to keys, other than the ones of the modules
+    pub actions: Vec<DebugAction>,
+    /// Between the server and client apps of Host mode, which share the plugin
+    #[cfg(feature = "enabled")]
+    pub(crate) host_link: HostDataLink,
+}
+
+/// Where a module is shown in the debug window. Can be created from its path, e.g.
+/// `.with_local("Physics/Colliders", ...)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugModuleInfo {
+    /// Categories and name of the module, separated by `/`
+    pub path: String,
+    /// Modules with a higher priority are shown first, categories are ordered by their first
+    /// module
+    pub priority: i32,
+}
+
+impl DebugModuleInfo {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            priority: 0,
+        }
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    pub fn categories(&self) -> impl Iterator<Item = &str> {
+        let mut segments = self.path.split('/');
+        segments.next_back();
+        segments
+    }
+}
+
+impl From<&str> for DebugModuleInfo {
+    fn from(path: &str) -> Self {
+        Self::new(path)
+    }
+}
+
+impl From<String> for DebugModuleInfo {
+    fn from(path: String) -> Self {
+        Self::new(path)
+    }
 }
 
 #[derive(Clone)]
@@ -48,14 +271,45 @@ pub enum DebugMode {
 
 #[derive(Clone)]
 pub struct ActiveModule {
-    pub name: String,
+    pub info: DebugModuleInfo,
     pub data: ComponentId,
     pub mode: DebugMode,
 }
 
+impl ActiveModule {
+    pub fn sample_always(&self) -> bool {
+        match &self.mode {
+            DebugMode::Server(remote) => remote.sample_always(),
+            DebugMode::Client(local) => local.sample_always(),
+        }
+    }
+
+    pub fn has_gizmos(&self) -> bool {
+        match &self.mode {
+            DebugMode::Server(remote) => remote.has_gizmos(),
+            DebugMode::Client(local) => local.has_gizmos(),
+        }
+    }
+
+    pub fn draw_gizmos(&self, world: &mut World, gizmos: &mut DebugGizmos) {
+        match &self.mode {
+            DebugMode::Server(remote) => remote.draw_gizmos(world, gizmos),
+            DebugMode::Client(local) => local.draw_gizmos(world, gizmos),
+        }
+    }
+}
+
 #[derive(Resource, Clone, Default)]
 pub struct DebugModules {
     pub modules: Vec<ActiveModule>,
+    /// Of the plugin and of every module
+    pub actions: Vec<DebugAction>,
+}
+
+impl Default for DebugZusammenPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DebugZusammenPlugin {
@@ -63,30 +317,94 @@ impl DebugZusammenPlugin {
         Self {
             locals: vec![],
             remote: vec![],
+            authorization: default(),
+            toggle_key: DEFAULT_TOGGLE_KEY,
+            layout_path: Some(DEFAULT_LAYOUT_PATH.into()),
+            snapshot_log: None,
+            recordings: default(),
+            observers: None,
+            actions: vec![],
+            #[cfg(feature = "enabled")]
+            host_link: default(),
         }
     }
 
+    /// Lets debug observers connect to the server (see [`crate::observer`])
+    pub fn with_observers(mut self, observers: ObserverConfig) -> Self {
+        self.observers = Some(observers);
+        self
+    }
+
+    /// Action bound to a key on the client, e.g. to toggle something in the game
+    pub fn with_action(mut self, action: DebugAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    pub fn with_recordings(mut self, recordings: RecordingConfig) -> Self {
+        self.recordings = recordings;
+        self
+    }
+
+    /// Makes the server log snapshots of the remote modules. Their update systems then always
+    /// run, whether a client is viewing them or not.
+    pub fn with_snapshot_log(mut self, snapshot_log: SnapshotLogConfig) -> Self {
+        self.snapshot_log = Some(snapshot_log);
+        self
+    }
+
+    pub fn with_toggle_key(mut self, toggle_key: KeyCode) -> Self {
+        self.toggle_key = toggle_key;
+        self
+    }
+
+    pub fn with_layout_path(mut self, layout_path: Option<PathBuf>) -> Self {
+        self.layout_path = layout_path;
+        self
+    }
+
+    pub fn with_authorization(mut self, authorization: DebugAuthorization) -> Self {
+        self.authorization = authorization;
+        self
+    }
+
     pub fn with_local<C: LocalDebugModule + 'static>(
         mut self,
-        name: impl Into<String>,
+        info: impl Into<DebugModuleInfo>,
         module: C,
     ) -> Self {
-        self.locals.push((name.into(), Arc::new(module)));
+        self.locals.push((info.into(), Arc::new(module)));
         self
     }
 
     pub fn with_remote<C: RemoteDebugModule + 'static>(
         mut self,
-        name: impl Into<String>,
+        info: impl Into<DebugModuleInfo>,
         module: C,
     ) -> Self {
-        self.remote.push((name.into(), Arc::new(module)));
+        self.remote.push((info.into(), Arc::new(module)));
         self
     }
 }
 
 impl ZusammenPlugin for DebugZusammenPlugin {
     fn add_protocol(&self, app: &mut App) {
+        app.add_channel::<DebugChannel>(ChannelSettings {
+            // Only the latest data matters, but it must eventually arrive
+            mode: ChannelMode::SequencedReliable(ReliableSettings::default()),
+            ..default()
+        });
+        app.add_channel::<DebugCommandChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..default()
+        });
+        app.register_message::<ViewedModules>(ChannelDirection::ClientToServer);
+        app.init_resource::<RemoteDataReplicators>();
+        app.init_resource::<DebugCommandForwarders>();
+
+        for (_, module) in &self.locals {
+            module.add_protocol(app);
+        }
         for (_, module) in &self.remote {
             module.add_protocol(app);
         }
@@ -94,41 +412,193 @@ impl ZusammenPlugin for DebugZusammenPlugin {
 
     fn add_shared(&self, _: &mut App) {}
 
+    #[cfg(feature = "enabled")]
     fn add_server(&self, app: &mut App) {
-        for (_, module) in &self.remote {
-            module.insert_data(app);
-            module.add_update_systems(app);
+        app.insert_resource(self.authorization.clone());
+        app.add_systems(Update, update_remote_data_targets);
+        let forwarders = app.world().resource::<DebugCommandForwarders>().0.clone();
+        for forward in forwarders {
+            forward(app);
+        }
+
+        for (_, module) in &self.locals {
+            module.add_server_systems(app);
         }
+
+        let mut debug_modules = DebugModules::default();
+        let mut viewers = DebugModuleViewers::default();
+        let mut host_shared = HashSet::new();
+        for (info, module) in &self.remote {
+            let data_id = module.insert_data(app);
+            configure_module_systems(app, data_id);
+            module.add_update_systems(app, DebugModuleSystems(data_id));
+            module.add_server_systems(app);
+            // Data that can be copied reaches the clients of Host mode through the link instead
+            let world = app.world();
+            if let (Some(data), Some(type_id)) = (
+                world.get_resource_by_id(data_id),
+                world
+                    .components()
+                    .get_info(data_id)
+                    .and_then(|info| info.type_id()),
+            ) {
+                if module.clone_data(data).is_some() {
+                    host_shared.insert(type_id);
+                }
+            }
+            if let Some(name) = data_name(app.world().components(), data_id) {
+                viewers.data.insert(name, data_id);
+            }
+            debug_modules.modules.push(ActiveModule {
+                info: info.clone(),
+                data: data_id,
+                mode: DebugMode::Server(module.clone()),
+            });
+        }
+
+        if let Some(snapshot_log) = &self.snapshot_log {
+            // Snapshots need every module's data to be up to date
+            viewers.always = true;
+            app.insert_resource(snapshot_log.clone());
+            app.add_systems(
+                Last,
+                log_debug_snapshot.run_if(on_real_timer(snapshot_log.interval)),
+            );
+        }
+        if let Some(observers) = &self.observers {
+            add_observer_server(app, observers);
+        }
+        app.add_systems(PreUpdate, receive_viewed_modules);
+
+        self.host_link.add_server(app, host_shared);
+        // After the modules' systems of Last, so that the client gets this frame's data
+        let mut write_host_data = write_host_data.into_configs();
+        for module in &debug_modules.modules {
+            write_host_data = write_host_data.after(DebugModuleSystems(module.data));
+        }
+        app.add_systems(Last, write_host_data);
+
+        app.insert_resource(viewers.enabled());
+        app.insert_resource(viewers);
+        app.insert_resource(debug_modules);
     }
 
+    #[cfg(not(feature = "enabled"))]
+    fn add_server(&self, _: &mut App) {}
+
+    #[cfg(feature = "enabled")]
     fn add_client(&self, app: &mut App) {
-        let mut debug_modules = DebugModules::default();
-        app.insert_resource(DebugMenuData { should_show: true });
+        self.add_menu(app);
+        app.init_resource::<RemoteDataUpdates>();
+        app.insert_resource(DebugRecorder::new(self.recordings.clone()));
+        self.host_link.add_client(app);
+        app.add_event::<DebugActionEvent>();
         // This one needs to be in update because it's an UI rendering system
-        app.add_systems(Update, debug_menu_system);
+        app.add_systems(
+            Update,
+            (
+                toggle_debug_menu.run_if(resource_exists::<ButtonInput<KeyCode>>),
+                trigger_debug_actions.run_if(resource_exists::<ButtonInput<KeyCode>>),
+                register_host_client,
+                read_host_data,
+                track_remote_data_updates,
+                debug_menu_system,
+                draw_debug_gizmos.run_if(resource_exists::<GizmoConfigStore>),
+                send_viewed_modules.run_if(in_state(client::NetworkingState::Connected)),
+            )
+                .chain(),
+        );
+        app.add_systems(
+            Last,
+            record_debug_frame.run_if(recording.and_then(on_real_timer(self.recordings.interval))),
+        );
 
-        for (name, module) in &self.locals {
-            let data_id = module.insert_data(app);
+        let debug_modules = self.insert_modules(app, false);
+        for module in &debug_modules.modules {
+            match &module.mode {
+                DebugMode::Client(local) => {
+                    configure_module_systems(app, module.data);
+                    local.add_update_systems(app, DebugModuleSystems(module.data));
+                    local.add_client_systems(app);
+                }
+                DebugMode::Server(remote) => remote.add_client_systems(app),
+            }
+        }
+        app.insert_resource(debug_modules);
+    }
+
+    #[cfg(not(feature = "enabled"))]
+    fn add_client(&self, _: &mut App) {}
+}
+
+impl DebugZusammenPlugin {
+    /// Resources and systems of the debug window, other than the ones of its modules
+    #[cfg(feature = "enabled")]
+    pub(crate) fn add_menu(&self, app: &mut App) {
+        let layout = self
+            .layout_path
+            .as_deref()
+            .and_then(DebugLayout::load)
+            .unwrap_or_default();
+        app.insert_resource(DebugMenuData {
+            toggle_key: self.toggle_key,
+            layout_path: self.layout_path.clone(),
+            layout,
+            search: String::new(),
+            rebinding: None,
+        });
+        app.init_resource::<EnabledDebugModules>();
+        app.add_systems(
+            Last,
+            save_debug_layout.run_if(on_real_timer(SAVE_LAYOUT_INTERVAL)),
+        );
+    }
+
+    /// Inserts the data of every module shown in the debug window, local and remote
+    /// `standalone` for the apps without a game, see [`LocalDebugModule::insert_standalone_data`]
+    #[cfg(feature = "enabled")]
+    pub(crate) fn insert_modules(&self, app: &mut App, standalone: bool) -> DebugModules {
+        let mut debug_modules = DebugModules {
+            actions: self.actions.clone(),
+            ..default()
+        };
+
+        for (info, module) in &self.locals {
+            let data_id = if standalone {
+                module.insert_standalone_data(app)
+            } else {
+                module.insert_data(app)
+            };
+            debug_modules.actions.extend(module.actions());
             debug_modules.modules.push(ActiveModule {
-                name: name.clone(),
+                info: info.clone(),
                 data: data_id,
                 mode: DebugMode::Client(module.clone()),
             });
         }
 
-        for (name, module) in &self.remote {
-            let data_id = module.insert_data(app);
+        for (info, module) in &self.remote {
+            let data_id = if standalone {
+                module.insert_standalone_data(app)
+            } else {
+                module.insert_data(app)
+            };
+            debug_modules.actions.extend(module.actions());
             debug_modules.modules.push(ActiveModule {
-                name: name.clone(),
+                info: info.clone(),
                 data: data_id,
                 mode: DebugMode::Server(module.clone()),
             });
         }
 
-        app.insert_resource(debug_modules);
-
-        for (_, module) in &self.locals {
-            module.add_update_systems(app);
+        for binding in conflicting_bindings(&debug_modules.actions, &default(), self.toggle_key) {
+            warn!("Debug key binding {binding} is used more than once");
         }
+
+        // Stable, so that modules with the same priority keep their registration order
+        debug_modules
+            .modules
+            .sort_by_key(|module| std::cmp::Reverse(module.info.priority));
+        debug_modules
     }
 }
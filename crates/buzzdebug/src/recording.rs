@@ -0,0 +1,417 @@
+//! Recordings of the data of the debug modules, e.g. to attach to a bug report. The debug window
+//! of a client records every module whose data can be serialized: all remote modules, and the
+//! local ones that implement `save_data`. [`DebugViewerPlugin`] plays recordings back through the
+//! modules' own UI.
+//!
+//! Remote modules only update while they are viewed (see [`crate::viewing`]), so the ones that
+//! matter should be open while recording. Only the latest [`RecordingConfig::max_frames`] frames
+//! are kept.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{prelude::*, scene::ron};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::{
+    bevy_egui::{EguiContext, EguiPlugin},
+    egui,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::plugin::{DebugMode, DebugModules};
+#[cfg(feature = "enabled")]
+use super::{
+    plugin::DebugZusammenPlugin,
+    ui::{debug_menu_system, draw_debug_gizmos, toggle_debug_menu},
+};
+
+/// Serializes module data as RON, see [`crate::typed::TypedDebugModule::save_data`]
+pub fn save_ron<D: Serialize>(data: &D) -> Option<String> {
+    ron::to_string(data)
+        .map_err(|e| error!("Could not serialize debug data: {e}"))
+        .ok()
+}
+
+/// Deserializes module data saved by [`save_ron`]
+pub fn load_ron<D: DeserializeOwned>(saved: &str) -> Result<D, String> {
+    ron::from_str(saved).map_err(|e| e.to_string())
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DebugRecording {
+    pub frames: Vec<RecordedFrame>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Since the start of the recording
+    pub elapsed: Duration,
+    /// Path and serialized data of each recorded module
+    pub modules: Vec<(String, String)>,
+}
+
+impl RecordedFrame {
+    /// Data of every module in [`DebugModules`] that can be recorded
+    pub fn capture(world: &World, elapsed: Duration) -> Self {
+        let debug_modules = world.resource::<DebugModules>();
+        let modules = debug_modules
+            .modules
+            .iter()
+            .filter_map(|module| {
+                let data = world.get_resource_by_id(module.data)?;
+                let saved = match &module.mode {
+                    DebugMode::Server(remote) => remote.save_data(data),
+                    DebugMode::Client(local) => local.save_data(data),
+                }?;
+                Some((module.info.path.clone(), saved))
+            })
+            .collect();
+
+        Self { elapsed, modules }
+    }
+
+    /// Replaces the data of the modules in [`DebugModules`] with the recorded one
+    pub fn load(&self, world: &mut World) {
+        world.resource_scope(|world, debug_modules: Mut<DebugModules>| {
+            for (path, saved) in &self.modules {
+                let Some(module) = debug_modules
+                    .modules
+                    .iter()
+                    .find(|module| &module.info.path == path)
+                else {
+                    continue;
+                };
+                let Some(data) = world.get_resource_mut_by_id(module.data) else {
+                    continue;
+                };
+                let result = match &module.mode {
+                    DebugMode::Server(remote) => remote.load_data(data, saved),
+                    DebugMode::Client(local) => local.load_data(data, saved),
+                };
+                if let Err(e) = result {
+                    warn!("Could not load the recorded data of {path}: {e}");
+                }
+            }
+        });
+    }
+}
+
+impl DebugRecording {
+    pub fn duration(&self) -> Duration {
+        self.frames
+            .last()
+            .map_or(Duration::ZERO, |frame| frame.elapsed)
+    }
+
+    /// Index of the frame shown at `position`, `None` before the first one
+    pub fn frame_at(&self, position: Duration) -> Option<usize> {
+        self.frames
+            .partition_point(|frame| frame.elapsed <= position)
+            .checked_sub(1)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, RecordingError> {
+        let contents = fs::read_to_string(path)?;
+        Ok(ron::from_str(&contents)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), RecordingError> {
+        let contents = ron::to_string(self)?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::Io(e) => write!(f, "could not access the recording: {e}"),
+            RecordingError::Parse(e) => write!(f, "invalid recording: {e}"),
+            RecordingError::Serialize(e) => write!(f, "could not serialize the recording: {e}"),
+        }
+    }
+}
+
+impl Error for RecordingError {}
+
+impl From<io::Error> for RecordingError {
+    fn from(e: io::Error) -> Self {
+        RecordingError::Io(e)
+    }
+}
+
+impl From<ron::error::SpannedError> for RecordingError {
+    fn from(e: ron::error::SpannedError) -> Self {
+        RecordingError::Parse(e)
+    }
+}
+
+impl From<ron::Error> for RecordingError {
+    fn from(e: ron::Error) -> Self {
+        RecordingError::Serialize(e)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RecordingConfig {
+    /// Time between recorded frames
+    pub interval: Duration,
+    /// Where recordings are saved
+    pub directory: PathBuf,
+    /// The oldest frames are dropped past this many, so that a recording left running doesn't
+    /// fill the memory
+    pub max_frames: usize,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(250),
+            directory: PathBuf::from("."),
+            // 10 minutes at the default interval
+            max_frames: 2400,
+        }
+    }
+}
+
+/// Recording in progress on the client, controlled from the debug window
+#[derive(Resource)]
+pub struct DebugRecorder {
+    pub config: RecordingConfig,
+    /// Start time and frames of the current recording
+    current: Option<(Duration, VecDeque<RecordedFrame>)>,
+    /// Where the last recording was saved, or why it couldn't be
+    pub last_saved: Option<Result<PathBuf, String>>,
+}
+
+impl DebugRecorder {
+    pub fn new(config: RecordingConfig) -> Self {
+        Self {
+            config,
+            current: None,
+            last_saved: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// `now` is the elapsed real time of the app
+    pub fn start(&mut self, now: Duration) {
+        self.current = Some((now, default()));
+    }
+
+    /// Saves the current recording to a new file in the configured directory
+    pub fn stop(&mut self) {
+        let Some((_, mut frames)) = self.current.take() else {
+            return;
+        };
+        // Frames may have been dropped from the start, the recording starts at the first one left
+        let start = frames.front().map_or(Duration::ZERO, |frame| frame.elapsed);
+        for frame in &mut frames {
+            frame.elapsed -= start;
+        }
+        let recording = DebugRecording {
+            frames: frames.into(),
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let path = self
+            .config
+            .directory
+            .join(format!("buzzdebug_recording_{timestamp}.ron"));
+
+        self.last_saved = Some(
+            recording
+                .save(&path)
+                .map(|_| path)
+                .map_err(|e| e.to_string()),
+        );
+    }
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn recording(recorder: Res<DebugRecorder>) -> bool {
+    recorder.is_recording()
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn record_debug_frame(world: &mut World) {
+    let now = world.resource::<Time<Real>>().elapsed();
+    world.resource_scope(|world, mut recorder: Mut<DebugRecorder>| {
+        let max_frames = recorder.config.max_frames;
+        let Some((started, frames)) = &mut recorder.current else {
+            return;
+        };
+
+        let frame = RecordedFrame::capture(world, now.saturating_sub(*started));
+        frames.push_back(frame);
+        while frames.len() > max_frames {
+            frames.pop_front();
+        }
+    });
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn recorder_controls(ui: &mut egui::Ui, recorder: &mut DebugRecorder, now: Duration) {
+    ui.horizontal(|ui| {
+        let current = recorder
+            .current
+            .as_ref()
+            .map(|(started, frames)| (now.saturating_sub(*started), frames.len()));
+
+        match current {
+            Some((duration, frames)) => {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!("Recording {:.0}s ({frames} frames)", duration.as_secs_f32()),
+                );
+                if ui.button("Stop and save").clicked() {
+                    recorder.stop();
+                }
+            }
+            None => {
+                if ui.button("Record").clicked() {
+                    recorder.start(now);
+                }
+                match &recorder.last_saved {
+                    Some(Ok(path)) => {
+                        ui.weak(format!("Saved {}", path.display()));
+                    }
+                    Some(Err(e)) => {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+                    None => {}
+                }
+            }
+        }
+    });
+}
+
+#[cfg(feature = "enabled")]
+/// Standalone app showing a recording through the modules that made it. Needs the same modules as
+/// the client that recorded it, e.g.
+/// `App::new().add_plugins((DefaultPlugins, DebugViewerPlugin { modules, recording }))`.
+pub struct DebugViewerPlugin {
+    pub modules: DebugZusammenPlugin,
+    pub recording: DebugRecording,
+}
+
+#[cfg(feature = "enabled")]
+/// Position of the viewer in the recording
+#[derive(Resource)]
+pub struct Playback {
+    pub recording: DebugRecording,
+    pub position: Duration,
+    pub playing: bool,
+    /// Frame whose data is currently in the module resources
+    applied: Option<usize>,
+}
+
+#[cfg(feature = "enabled")]
+impl Plugin for DebugViewerPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+
+        self.modules.add_menu(app);
+        let debug_modules = self.modules.insert_modules(app, true);
+        app.insert_resource(debug_modules);
+        app.insert_resource(Playback {
+            recording: self.recording.clone(),
+            position: Duration::ZERO,
+            playing: false,
+            applied: None,
+        });
+        app.add_systems(
+            Update,
+            (
+                toggle_debug_menu.run_if(resource_exists::<ButtonInput<KeyCode>>),
+                playback_controls,
+                apply_playback_frame,
+                debug_menu_system,
+                draw_debug_gizmos.run_if(resource_exists::<GizmoConfigStore>),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[cfg(feature = "enabled")]
+fn playback_controls(
+    mut contexts: Query<&mut EguiContext>,
+    time: Res<Time<Real>>,
+    mut playback: ResMut<Playback>,
+) {
+    let playback = &mut *playback;
+    let duration = playback.recording.duration();
+    if playback.playing {
+        playback.position += time.delta();
+        if playback.position >= duration {
+            playback.position = duration;
+            playback.playing = false;
+        }
+    }
+
+    let Ok(mut ctx) = contexts.get_single_mut() else {
+        return;
+    };
+    egui::TopBottomPanel::bottom("buzzdebug_playback").show(ctx.get_mut(), |ui| {
+        ui.horizontal(|ui| {
+            let label = if playback.playing { "Pause" } else { "Play" };
+            if ui.button(label).clicked() {
+                if !playback.playing && playback.position >= duration {
+                    playback.position = Duration::ZERO;
+                }
+                playback.playing = !playback.playing;
+            }
+
+            let mut seconds = playback.position.as_secs_f32();
+            let slider = egui::Slider::new(&mut seconds, 0.0..=duration.as_secs_f32()).suffix("s");
+            if ui.add(slider).changed() {
+                playback.position = Duration::from_secs_f32(seconds);
+            }
+
+            match playback.recording.frame_at(playback.position) {
+                Some(frame) => ui.label(format!(
+                    "Frame {}/{}",
+                    frame + 1,
+                    playback.recording.frames.len()
+                )),
+                None => ui.label(format!("Frame -/{}", playback.recording.frames.len())),
+            };
+        });
+    });
+}
+
+#[cfg(feature = "enabled")]
+fn apply_playback_frame(world: &mut World) {
+    world.resource_scope(|world, mut playback: Mut<Playback>| {
+        let frame = playback.recording.frame_at(playback.position);
+        if frame == playback.applied {
+            return;
+        }
+        playback.applied = frame;
+        let Some(frame) = frame else {
+            return;
+        };
+
+        playback.recording.frames[frame].load(world);
+    });
+}
@@ -0,0 +1,139 @@
+//! Transport of remote module data from the server to the clients, through lightyear's resource
+//! replication. The data only goes to the clients the server admitted and whose address the
+//! [`DebugAuthorization`](crate::commands::DebugAuthorization) allows. Clients of Host mode get
+//! the data from the server app directly instead (see [`crate::host`]).
+
+use std::{any::TypeId, time::Duration};
+
+#[cfg(feature = "enabled")]
+use bevy::utils::HashSet;
+use bevy::{
+    ecs::component::{ComponentId, Tick},
+    prelude::*,
+    utils::HashMap,
+};
+#[cfg(feature = "enabled")]
+use lightyear::prelude::server::ServerConnections;
+use lightyear::prelude::{server::ReplicateResourceExt, *};
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "enabled")]
+use zusammen_app::{client_ip, AdmittedClients};
+
+#[cfg(feature = "enabled")]
+use super::{
+    commands::DebugAuthorization,
+    host::HostDataWriter,
+    plugin::{DebugMode, DebugModules},
+};
+
+/// Channel used by buzzdebug, separate from the game's own channels
+#[derive(Channel)]
+pub struct DebugChannel;
+
+/// Data resource of a debug module that can leave the process it was created in
+pub trait DebugData: Resource + Serialize + DeserializeOwned + Clone {}
+
+impl<T: Resource + Serialize + DeserializeOwned + Clone> DebugData for T {}
+
+/// Functions setting the clients each registered remote data resource is replicated to, on the
+/// server, with the type of the resource
+#[derive(Resource, Default)]
+pub(crate) struct RemoteDataReplicators(pub Vec<(TypeId, fn(&mut Commands, NetworkTarget))>);
+
+/// Registers `R` as the data of a remote module, so that it's replicated from the server to the
+/// clients. Meant to be called from [`RemoteDebugModule::add_protocol`].
+///
+/// [`RemoteDebugModule::add_protocol`]: super::plugin::RemoteDebugModule::add_protocol
+pub fn register_remote_data<R: DebugData>(app: &mut App) {
+    app.register_resource::<R>(ChannelDirection::ServerToClient);
+    app.world_mut()
+        .get_resource_or_insert_with(RemoteDataReplicators::default)
+        .0
+        .push((TypeId::of::<R>(), replicate_to::<R>));
+}
+
+fn replicate_to<R: DebugData>(commands: &mut Commands, target: NetworkTarget) {
+    commands.replicate_resource::<R, DebugChannel>(target);
+}
+
+/// Keeps the remote data replicated to the admitted clients that are authorized to debug, as
+/// they join and leave. The data handed over through the host link skips the clients on it.
+#[cfg(feature = "enabled")]
+pub(crate) fn update_remote_data_targets(
+    mut commands: Commands,
+    replicators: Res<RemoteDataReplicators>,
+    admitted: Res<AdmittedClients>,
+    authorization: Res<DebugAuthorization>,
+    connections: Res<ServerConnections>,
+    host_writer: Option<Res<HostDataWriter>>,
+    mut current: Local<Option<(HashSet<ClientId>, HashSet<ClientId>)>>,
+) {
+    let clients: HashSet<_> = admitted
+        .iter()
+        .filter(|client_id| authorization.allows(client_ip(&connections, *client_id)))
+        .collect();
+    let linked = host_writer
+        .as_ref()
+        .map(|writer| writer.linked_clients())
+        .unwrap_or_default();
+    if current
+        .as_ref()
+        .is_some_and(|current| current.0 == clients && current.1 == linked)
+    {
+        return;
+    }
+
+    let all = NetworkTarget::Only(clients.iter().copied().collect());
+    let unlinked = NetworkTarget::Only(clients.difference(&linked).copied().collect());
+    for (data, replicate) in &replicators.0 {
+        let shared = host_writer
+            .as_ref()
+            .is_some_and(|writer| writer.shares(*data));
+        let target = if shared { &unlinked } else { &all };
+        replicate(&mut commands, target.clone());
+    }
+    *current = Some((clients, linked));
+}
+
+/// When each remote data resource was last received on the client
+#[derive(Resource, Default)]
+pub struct RemoteDataUpdates {
+    /// Change tick of the resource and elapsed real time at which that change was seen. The time
+    /// is `None` until the server's data replaces the one inserted by the client.
+    updates: HashMap<ComponentId, (Tick, Option<Duration>)>,
+}
+
+impl RemoteDataUpdates {
+    /// Time since the data was last received, `None` if it never was
+    pub fn staleness(&self, data: ComponentId, now: Duration) -> Option<Duration> {
+        let (_, received_at) = self.updates.get(&data)?;
+        received_at.map(|received_at| now.saturating_sub(received_at))
+    }
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn track_remote_data_updates(world: &mut World) {
+    let now = world.resource::<Time<Real>>().elapsed();
+
+    world.resource_scope(|world, mut updates: Mut<RemoteDataUpdates>| {
+        let debug_modules = world.resource::<DebugModules>();
+        for module in &debug_modules.modules {
+            let DebugMode::Server(_) = module.mode else {
+                continue;
+            };
+            let Some(ticks) = world.get_resource_change_ticks_by_id(module.data) else {
+                continue;
+            };
+
+            // The first change seen is the client inserting its own placeholder data
+            let (tick, received_at) = updates
+                .updates
+                .entry(module.data)
+                .or_insert((ticks.changed, None));
+            if *tick != ticks.changed {
+                *tick = ticks.changed;
+                *received_at = Some(now);
+            }
+        }
+    });
+}
@@ -0,0 +1,113 @@
+//! Text output of the debug modules, for dedicated servers that have no UI to show them in. The
+//! server can periodically log a [`DebugSnapshot`] of all remote modules, see
+//! [`DebugZusammenPlugin::with_snapshot_log`](crate::plugin::DebugZusammenPlugin::with_snapshot_log).
+
+use std::{fmt, path::PathBuf, time::Duration};
+#[cfg(feature = "enabled")]
+use std::{fs::OpenOptions, io::Write};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::plugin::{DebugMode, DebugModules};
+
+/// Text rendering of every module at some point in time
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DebugSnapshot {
+    /// Real time since the app started
+    pub elapsed: Duration,
+    pub modules: Vec<ModuleSnapshot>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ModuleSnapshot {
+    pub path: String,
+    /// `None` if the module has no text rendering
+    pub text: Option<String>,
+}
+
+impl DebugSnapshot {
+    pub fn take(world: &World) -> Self {
+        let debug_modules = world.resource::<DebugModules>();
+        let modules = debug_modules
+            .modules
+            .iter()
+            .map(|module| {
+                let text =
+                    world
+                        .get_resource_by_id(module.data)
+                        .and_then(|data| match &module.mode {
+                            DebugMode::Server(remote) => remote.render_text(data),
+                            DebugMode::Client(local) => local.render_text(data),
+                        });
+                ModuleSnapshot {
+                    path: module.info.path.clone(),
+                    text,
+                }
+            })
+            .collect();
+
+        Self {
+            elapsed: world.resource::<Time<Real>>().elapsed(),
+            modules,
+        }
+    }
+}
+
+impl fmt::Display for DebugSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "=== Buzzdebug at {:.1}s ===", self.elapsed.as_secs_f32())?;
+        for module in &self.modules {
+            writeln!(f, "--- {} ---", module.path)?;
+            match &module.text {
+                Some(text) => writeln!(f, "{text}")?,
+                None => writeln!(f, "(no text output)")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum SnapshotOutput {
+    /// Through `bevy::log`, at the info level
+    Log,
+    Stdout,
+    /// Appended to the file
+    File(PathBuf),
+}
+
+/// How the server logs snapshots of the remote modules
+#[derive(Resource, Clone, Debug)]
+pub struct SnapshotLogConfig {
+    pub interval: Duration,
+    pub output: SnapshotOutput,
+}
+
+impl Default for SnapshotLogConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            output: SnapshotOutput::Log,
+        }
+    }
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn log_debug_snapshot(world: &World) {
+    let snapshot = DebugSnapshot::take(world);
+    match &world.resource::<SnapshotLogConfig>().output {
+        SnapshotOutput::Log => info!("{snapshot}"),
+        SnapshotOutput::Stdout => println!("{snapshot}"),
+        SnapshotOutput::File(path) => {
+            let result = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{snapshot}"));
+            if let Err(e) = result {
+                error!("Could not write debug snapshot to {}: {e}", path.display());
+            }
+        }
+    }
+}
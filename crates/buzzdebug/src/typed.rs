@@ -0,0 +1,290 @@
+//! Debug modules that work directly with their data resource, without `ComponentId`s or
+//! pointers. Anything implementing [`TypedDebugModule`] is a [`LocalDebugModule`], and also a
+//! [`RemoteDebugModule`] if its data can be replicated.
+
+use std::any::Any;
+
+use bevy::{
+    ecs::{change_detection::MutUntyped, component::ComponentId},
+    prelude::*,
+    ptr::Ptr,
+    scene::ron,
+};
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+
+#[cfg(feature = "enabled")]
+use super::commands::DebugCommands;
+use super::{
+    hotkeys::DebugAction,
+    plugin::{DebugGizmos, LocalDebugModule, RemoteDebugModule},
+    recording::{load_ron, save_ron},
+    replication::{register_remote_data, DebugData},
+    viewing::DebugModuleSystems,
+};
+
+pub trait TypedDebugModule: Send + Sync {
+    type Data: Resource + FromWorld;
+
+    /// Protocol items needed by the module, other than the data resource of remote modules which
+    /// is registered automatically
+    fn add_protocol(&self, _: &mut App) {}
+
+    fn insert_data(&self, app: &mut App) {
+        app.init_resource::<Self::Data>();
+    }
+
+    /// See [`LocalDebugModule::insert_standalone_data`]
+    fn insert_standalone_data(&self, app: &mut App) {
+        self.insert_data(app);
+    }
+
+    /// The systems have to be added to `set`, so that they only run while the module is viewed
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems);
+
+    /// See [`LocalDebugModule::add_server_systems`]
+    fn add_server_systems(&self, _: &mut App) {}
+
+    /// See [`LocalDebugModule::add_client_systems`]
+    fn add_client_systems(&self, _: &mut App) {}
+
+    /// See [`LocalDebugModule::sample_always`]
+    fn sample_always(&self) -> bool {
+        false
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: &Self::Data);
+
+    /// Plain text version of the UI, for snapshots (see [`crate::snapshot`]). Remote modules that
+    /// don't implement it are written as RON instead.
+    fn render_text(&self, _: &Self::Data) -> Option<String> {
+        None
+    }
+
+    /// Serialized data for recordings (see [`crate::recording`]). Remote modules that don't
+    /// implement it are recorded as RON. Local ones are only recorded if they implement it, e.g.
+    /// with [`save_ron`].
+    fn save_data(&self, _: &Self::Data) -> Option<String> {
+        None
+    }
+
+    /// Data serialized by [`TypedDebugModule::save_data`], e.g. with [`load_ron`]
+    fn load_data(&self, _: &str) -> Result<Self::Data, String> {
+        Err("the module can't be recorded".to_string())
+    }
+
+    /// Same as [`TypedDebugModule::render_ui`], with a way to send commands to the server (see
+    /// [`crate::commands`]). Only used by remote modules.
+    #[cfg(feature = "enabled")]
+    fn render_ui_with_commands(&self, ui: &mut egui::Ui, data: &Self::Data, _: &mut DebugCommands) {
+        self.render_ui(ui, data);
+    }
+
+    /// Same as [`TypedDebugModule::render_ui`], but allowed to edit the data. Only dereference
+    /// it mutably (which marks it as changed) when something was actually edited. Only used by
+    /// local modules.
+    #[cfg(feature = "enabled")]
+    fn render_ui_mut(&self, ui: &mut egui::Ui, data: Mut<Self::Data>) {
+        self.render_ui(ui, &data);
+    }
+
+    /// Whether [`TypedDebugModule::draw_gizmos`] draws anything, which makes the overlays
+    /// toggleable from the debug window
+    fn has_gizmos(&self) -> bool {
+        false
+    }
+
+    /// World-space overlays, drawn on the client every frame while they are toggled on
+    fn draw_gizmos(&self, _: &mut Gizmos, _: &Self::Data) {}
+
+    /// Actions that can be bound to keys on the client (see [`crate::hotkeys`])
+    fn actions(&self) -> Vec<DebugAction> {
+        vec![]
+    }
+}
+
+fn data_id<R: Resource>(app: &App) -> ComponentId {
+    app.world()
+        .components()
+        .resource_id::<R>()
+        .expect("TypedDebugModule::insert_data should insert the data resource")
+}
+
+fn draw_typed_gizmos<T: TypedDebugModule>(module: &T, world: &mut World, gizmos: &mut DebugGizmos) {
+    if !world.contains_resource::<T::Data>() {
+        return;
+    }
+    // Out of the world, which the gizmos borrow
+    world.resource_scope(|world, data: Mut<T::Data>| {
+        module.draw_gizmos(&mut gizmos.get_mut(world), &data);
+    });
+}
+
+impl<T: TypedDebugModule> LocalDebugModule for T {
+    fn add_protocol(&self, app: &mut App) {
+        TypedDebugModule::add_protocol(self, app);
+    }
+
+    fn insert_data(&self, app: &mut App) -> ComponentId {
+        TypedDebugModule::insert_data(self, app);
+        data_id::<T::Data>(app)
+    }
+
+    fn insert_standalone_data(&self, app: &mut App) -> ComponentId {
+        TypedDebugModule::insert_standalone_data(self, app);
+        data_id::<T::Data>(app)
+    }
+
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems) {
+        TypedDebugModule::add_update_systems(self, app, set);
+    }
+
+    fn add_server_systems(&self, app: &mut App) {
+        TypedDebugModule::add_server_systems(self, app);
+    }
+
+    fn add_client_systems(&self, app: &mut App) {
+        TypedDebugModule::add_client_systems(self, app);
+    }
+
+    fn sample_always(&self) -> bool {
+        TypedDebugModule::sample_always(self)
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: Ptr) {
+        // SAFETY: `data` points to the resource whose id `insert_data` returned
+        TypedDebugModule::render_ui(self, ui, unsafe { data.deref::<T::Data>() });
+    }
+
+    fn render_text(&self, data: Ptr) -> Option<String> {
+        // SAFETY: same as above
+        TypedDebugModule::render_text(self, unsafe { data.deref::<T::Data>() })
+    }
+
+    fn save_data(&self, data: Ptr) -> Option<String> {
+        // SAFETY: same as above
+        TypedDebugModule::save_data(self, unsafe { data.deref::<T::Data>() })
+    }
+
+    fn load_data(&self, data: MutUntyped, saved: &str) -> Result<(), String> {
+        let loaded = TypedDebugModule::load_data(self, saved)?;
+        // SAFETY: same as above
+        *unsafe { data.with_type::<T::Data>() } = loaded;
+        Ok(())
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui_mut(&self, ui: &mut egui::Ui, data: MutUntyped) {
+        // SAFETY: same as above
+        TypedDebugModule::render_ui_mut(self, ui, unsafe { data.with_type::<T::Data>() });
+    }
+
+    fn has_gizmos(&self) -> bool {
+        TypedDebugModule::has_gizmos(self)
+    }
+
+    fn draw_gizmos(&self, world: &mut World, gizmos: &mut DebugGizmos) {
+        draw_typed_gizmos(self, world, gizmos);
+    }
+
+    fn actions(&self) -> Vec<DebugAction> {
+        TypedDebugModule::actions(self)
+    }
+}
+
+impl<T> RemoteDebugModule for T
+where
+    T: TypedDebugModule,
+    T::Data: DebugData,
+{
+    fn add_protocol(&self, app: &mut App) {
+        register_remote_data::<T::Data>(app);
+        TypedDebugModule::add_protocol(self, app);
+    }
+
+    fn insert_data(&self, app: &mut App) -> ComponentId {
+        TypedDebugModule::insert_data(self, app);
+        data_id::<T::Data>(app)
+    }
+
+    fn insert_standalone_data(&self, app: &mut App) -> ComponentId {
+        TypedDebugModule::insert_standalone_data(self, app);
+        data_id::<T::Data>(app)
+    }
+
+    fn add_update_systems(&self, app: &mut App, set: DebugModuleSystems) {
+        TypedDebugModule::add_update_systems(self, app, set);
+    }
+
+    fn add_server_systems(&self, app: &mut App) {
+        TypedDebugModule::add_server_systems(self, app);
+    }
+
+    fn add_client_systems(&self, app: &mut App) {
+        TypedDebugModule::add_client_systems(self, app);
+    }
+
+    fn sample_always(&self) -> bool {
+        TypedDebugModule::sample_always(self)
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui(&self, ui: &mut egui::Ui, data: Ptr) {
+        // SAFETY: `data` points to the resource whose id `insert_data` returned
+        TypedDebugModule::render_ui(self, ui, unsafe { data.deref::<T::Data>() });
+    }
+
+    fn render_text(&self, data: Ptr) -> Option<String> {
+        // SAFETY: same as above
+        let data = unsafe { data.deref::<T::Data>() };
+        TypedDebugModule::render_text(self, data)
+            .or_else(|| ron::ser::to_string_pretty(data, default()).ok())
+    }
+
+    fn save_data(&self, data: Ptr) -> Option<String> {
+        // SAFETY: same as above
+        let data = unsafe { data.deref::<T::Data>() };
+        TypedDebugModule::save_data(self, data).or_else(|| save_ron(data))
+    }
+
+    fn load_data(&self, data: MutUntyped, saved: &str) -> Result<(), String> {
+        let loaded = TypedDebugModule::load_data(self, saved).or_else(|_| load_ron(saved))?;
+        // SAFETY: same as above
+        *unsafe { data.with_type::<T::Data>() } = loaded;
+        Ok(())
+    }
+
+    #[cfg(feature = "enabled")]
+    fn render_ui_with_commands(&self, ui: &mut egui::Ui, data: Ptr, commands: &mut DebugCommands) {
+        // SAFETY: same as above
+        let data = unsafe { data.deref::<T::Data>() };
+        TypedDebugModule::render_ui_with_commands(self, ui, data, commands);
+    }
+
+    fn has_gizmos(&self) -> bool {
+        TypedDebugModule::has_gizmos(self)
+    }
+
+    fn draw_gizmos(&self, world: &mut World, gizmos: &mut DebugGizmos) {
+        draw_typed_gizmos(self, world, gizmos);
+    }
+
+    fn actions(&self) -> Vec<DebugAction> {
+        TypedDebugModule::actions(self)
+    }
+
+    fn clone_data(&self, data: Ptr) -> Option<Box<dyn Any + Send + Sync>> {
+        // SAFETY: same as above
+        Some(Box::new(unsafe { data.deref::<T::Data>() }.clone()))
+    }
+
+    fn set_data(&self, data: MutUntyped, copy: &dyn Any) {
+        let Some(copy) = copy.downcast_ref::<T::Data>() else {
+            return;
+        };
+        // SAFETY: same as above
+        *unsafe { data.with_type::<T::Data>() } = copy.clone();
+    }
+}
@@ -1,38 +1,521 @@
-use bevy::prelude::*;
+#[cfg(feature = "enabled")]
+use std::time::Duration;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "enabled")]
+use bevy::utils::HashSet;
+use bevy::{prelude::*, scene::ron};
+#[cfg(feature = "enabled")]
 use bevy_inspector_egui::{bevy_egui::EguiContext, egui};
+#[cfg(feature = "enabled")]
+use lightyear::prelude::client;
+use serde::{Deserialize, Serialize};
+
+use super::hotkeys::KeyBinding;
+#[cfg(feature = "enabled")]
+use super::{
+    commands::DebugCommands,
+    hotkeys::hotkeys_panel,
+    plugin::{ActiveModule, DebugGizmos, DebugMode, DebugModules},
+    recording::{recorder_controls, DebugRecorder},
+    replication::RemoteDataUpdates,
+    viewing::EnabledDebugModules,
+};
+
+#[cfg(feature = "enabled")]
+/// Remote data older than this is shown as stale
+const STALE_AFTER: Duration = Duration::from_secs(2);
+#[cfg(feature = "enabled")]
+/// How often the layout is checked for changes to save. Dragging a window changes it every frame.
+pub(crate) const SAVE_LAYOUT_INTERVAL: Duration = Duration::from_secs(1);
 
-use super::plugin::{DebugMode, DebugModules};
+pub const DEFAULT_TOGGLE_KEY: KeyCode = KeyCode::F12;
+/// Relative to the working directory
+pub const DEFAULT_LAYOUT_PATH: &str = "buzzdebug_layout.ron";
 
 #[derive(Resource)]
 pub struct DebugMenuData {
+    pub toggle_key: KeyCode,
+    /// File the layout is loaded from and saved to. It's not persisted if `None`.
+    pub layout_path: Option<PathBuf>,
+    pub layout: DebugLayout,
+    /// Contents of the search box, modules matching it are shown instead of the categories
+    pub search: String,
+    /// Action whose binding is replaced by the next key pressed
+    pub rebinding: Option<String>,
+}
+
+/// State of the debug UI that is kept between runs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DebugLayout {
     pub should_show: bool,
+    pub menu: WindowLayout,
+    /// By module path
+    pub modules: BTreeMap<String, ModuleLayout>,
+    /// Paths of the categories expanded in the main window
+    pub open_categories: BTreeSet<String>,
+    /// Paths of the modules shown above the categories
+    pub favorites: BTreeSet<String>,
+    /// Bindings of the actions changed from their default, by action id
+    pub bindings: BTreeMap<String, Option<KeyBinding>>,
 }
 
-pub fn debug_menu_system(world: &mut World) {
-    if !world.resource::<DebugMenuData>().should_show {
-        return;
-    };
+impl Default for DebugLayout {
+    fn default() -> Self {
+        Self {
+            should_show: true,
+            menu: default(),
+            modules: default(),
+            open_categories: default(),
+            favorites: default(),
+            bindings: default(),
+        }
+    }
+}
 
-    let unsafe_world_cell = world.as_unsafe_world_cell();
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowLayout {
+    pub position: Option<[f32; 2]>,
+    pub collapsed: bool,
+}
 
-    let mut egui_ctx = unsafe { unsafe_world_cell.world_mut() }.query::<&mut EguiContext>();
-    let Ok(mut ctx) = egui_ctx.get_single_mut(unsafe { unsafe_world_cell.world_mut() }) else {
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModuleLayout {
+    /// Expanded in the main window
+    pub open: bool,
+    /// Shown in its own window instead of the main one
+    pub detached: bool,
+    /// Its world-space overlays are drawn, for modules that have them
+    pub gizmos: bool,
+    /// Its update systems don't run, even while it's viewed
+    pub disabled: bool,
+    pub window: WindowLayout,
+}
+
+impl DebugLayout {
+    /// `None` if there is no layout saved at `path` or it can't be read
+    pub fn load(path: &Path) -> Option<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                warn!("Could not read debug layout {}: {e}", path.display());
+                return None;
+            }
+        };
+        ron::from_str(&contents)
+            .map_err(|e| warn!("Could not parse debug layout {}: {e}", path.display()))
+            .ok()
+    }
+
+    pub fn save(&self, path: &Path) {
+        let contents = match ron::ser::to_string_pretty(self, default()) {
+            Ok(contents) => contents,
+            Err(e) => {
+                error!("Could not serialize debug layout: {e}");
+                return;
+            }
+        };
+        if let Err(e) = fs::write(path, contents) {
+            warn!("Could not save debug layout {}: {e}", path.display());
+        }
+    }
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn toggle_debug_menu(keys: Res<ButtonInput<KeyCode>>, mut menu: ResMut<DebugMenuData>) {
+    // The key is being bound to an action
+    if menu.rebinding.is_some() {
+        return;
+    }
+    // With modifiers, it can be the binding of an action
+    if keys.just_pressed(menu.toggle_key)
+        && KeyBinding::pressed_with(menu.toggle_key, &keys) == KeyBinding::new(menu.toggle_key)
+    {
+        menu.layout.should_show = !menu.layout.should_show;
+    }
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn save_debug_layout(menu: Res<DebugMenuData>, mut saved: Local<Option<DebugLayout>>) {
+    let Some(path) = &menu.layout_path else {
         return;
     };
+    if saved.as_ref() == Some(&menu.layout) {
+        return;
+    }
+    menu.layout.save(path);
+    *saved = Some(menu.layout.clone());
+}
 
-    let debug_modules = unsafe { unsafe_world_cell.world() }.resource::<DebugModules>();
+#[cfg(feature = "enabled")]
+pub fn debug_menu_system(world: &mut World) {
+    let viewed = world.resource_scope(|world, mut menu: Mut<DebugMenuData>| {
+        // The layout is saved by comparing it, no need to trigger change detection every frame
+        let DebugMenuData {
+            toggle_key,
+            layout,
+            search,
+            rebinding,
+            ..
+        } = menu.bypass_change_detection();
+        if !layout.should_show {
+            return default();
+        };
 
-    egui::Window::new("Buzzdebug!").show(ctx.get_mut(), |ui| {
-        for module in &debug_modules.modules {
-            let data = unsafe { unsafe_world_cell.world() }
-                .get_resource_by_id(module.data)
-                .unwrap();
-            ui.collapsing(&module.name, |ui| {
-                match &module.mode {
-                    DebugMode::Server(module) => module.render_ui(ui, data),
-                    DebugMode::Client(module) => module.render_ui(ui, data),
+        let unsafe_world_cell = world.as_unsafe_world_cell();
+
+        let mut egui_ctx = unsafe { unsafe_world_cell.world_mut() }.query::<&mut EguiContext>();
+        let Ok(mut ctx) = egui_ctx.get_single_mut(unsafe { unsafe_world_cell.world_mut() }) else {
+            return default();
+        };
+        let ctx = ctx.get_mut();
+
+        let debug_modules = unsafe { unsafe_world_cell.world() }.resource::<DebugModules>();
+        // Not there when playing back a recording
+        let remote_updates =
+            unsafe { unsafe_world_cell.world() }.get_resource::<RemoteDataUpdates>();
+        let now = unsafe { unsafe_world_cell.world() }
+            .resource::<Time<Real>>()
+            .elapsed();
+
+        let mut commands = DebugCommands::default();
+        // Modules whose UI is drawn this frame
+        let mut viewed = HashSet::new();
+
+        let mut render_module = |ui: &mut egui::Ui, module: &ActiveModule| {
+            viewed.insert(module.data);
+            match &module.mode {
+                DebugMode::Server(remote) => {
+                    if let Some(remote_updates) = remote_updates {
+                        staleness_label(ui, remote_updates.staleness(module.data, now));
+                    }
+                    let data = unsafe { unsafe_world_cell.world() }
+                        .get_resource_by_id(module.data)
+                        .unwrap();
+                    remote.render_ui_with_commands(ui, data, &mut commands);
+                }
+                DebugMode::Client(local) => {
+                    let data =
+                        unsafe { unsafe_world_cell.get_resource_mut_by_id(module.data) }.unwrap();
+                    local.render_ui_mut(ui, data);
+                }
+            };
+        };
+
+        layout_window(
+            ctx,
+            "Buzzdebug!",
+            egui::Id::new("buzzdebug"),
+            &mut layout.menu,
+            None,
+            |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Search:");
+                    ui.text_edit_singleline(search);
+                });
+                if let Some(mut recorder) =
+                    unsafe { unsafe_world_cell.get_resource_mut::<DebugRecorder>() }
+                {
+                    recorder_controls(ui, &mut recorder, now);
+                }
+                if !debug_modules.actions.is_empty() {
+                    egui::CollapsingHeader::new("Hotkeys")
+                        .id_source("buzzdebug_hotkeys")
+                        .show(ui, |ui| {
+                            hotkeys_panel(
+                                ui,
+                                &debug_modules.actions,
+                                &mut layout.bindings,
+                                rebinding,
+                                *toggle_key,
+                            );
+                        });
+                }
+                ui.separator();
+
+                let mut entries = ModuleEntries {
+                    modules: &mut layout.modules,
+                    open_categories: &mut layout.open_categories,
+                    favorites: &mut layout.favorites,
+                    render_module: &mut render_module,
                 };
-            });
+
+                if !search.is_empty() {
+                    let search = search.to_lowercase();
+                    for module in &debug_modules.modules {
+                        if module.info.path.to_lowercase().contains(&search) {
+                            entries.module(ui, module, &module.info.path);
+                        }
+                    }
+                    return;
+                }
+
+                let mut root = Category::default();
+                let mut any_favorite = false;
+                for module in &debug_modules.modules {
+                    if entries.favorites.contains(&module.info.path) {
+                        entries.module(ui, module, &module.info.path);
+                        any_favorite = true;
+                    } else {
+                        root.insert(module);
+                    }
+                }
+                if any_favorite {
+                    ui.separator();
+                }
+                entries.category(ui, &root, "");
+            },
+        );
+
+        for module in &debug_modules.modules {
+            let module_layout = layout.modules.entry(module.info.path.clone()).or_default();
+            if !module_layout.detached {
+                continue;
+            }
+            // Closing a detached window puts the module back in the main one
+            let mut open = true;
+            layout_window(
+                ctx,
+                &module.info.path,
+                egui::Id::new(("buzzdebug", &module.info.path)),
+                &mut module_layout.window,
+                Some(&mut open),
+                |ui| render_module(ui, module),
+            );
+            module_layout.detached = open;
+        }
+
+        // Overlays need up to date data even when the module's UI is collapsed
+        for module in &debug_modules.modules {
+            if gizmos_enabled(layout, module) {
+                viewed.insert(module.data);
+            }
         }
+
+        if let Some(mut connection) =
+            unsafe { unsafe_world_cell.get_resource_mut::<client::ConnectionManager>() }
+        {
+            commands.apply(&mut connection);
+        }
+
+        viewed
     });
+
+    let layout = &world.resource::<DebugMenuData>().layout;
+    let enabled = world
+        .resource::<DebugModules>()
+        .modules
+        .iter()
+        .filter(|module| {
+            !module_disabled(layout, module)
+                && (viewed.contains(&module.data) || module.sample_always())
+        })
+        .map(|module| module.data)
+        .collect();
+    world
+        .resource_mut::<EnabledDebugModules>()
+        .set_if_neq(EnabledDebugModules(enabled));
+}
+
+#[cfg(feature = "enabled")]
+fn module_disabled(layout: &DebugLayout, module: &ActiveModule) -> bool {
+    layout
+        .modules
+        .get(&module.info.path)
+        .is_some_and(|module_layout| module_layout.disabled)
+}
+
+#[cfg(feature = "enabled")]
+/// Whether the overlays of `module` are drawn. They are hidden along with the debug window.
+fn gizmos_enabled(layout: &DebugLayout, module: &ActiveModule) -> bool {
+    layout.should_show
+        && module.has_gizmos()
+        && layout
+            .modules
+            .get(&module.info.path)
+            .is_some_and(|module_layout| module_layout.gizmos)
+}
+
+#[cfg(feature = "enabled")]
+/// Draws the overlays of the modules that have them toggled on
+pub(crate) fn draw_debug_gizmos(world: &mut World, mut gizmos: Local<DebugGizmos>) {
+    world.resource_scope(|world, debug_modules: Mut<DebugModules>| {
+        for module in &debug_modules.modules {
+            if gizmos_enabled(&world.resource::<DebugMenuData>().layout, module) {
+                module.draw_gizmos(world, &mut gizmos);
+            }
+        }
+    });
+
+    // The gizmos are buffered until applied
+    gizmos.apply(world);
+}
+
+#[cfg(feature = "enabled")]
+/// Modules grouped by category, in the order of [`DebugModules::modules`]
+#[derive(Default)]
+struct Category<'a> {
+    children: Vec<(&'a str, Category<'a>)>,
+    modules: Vec<&'a ActiveModule>,
+}
+
+#[cfg(feature = "enabled")]
+impl<'a> Category<'a> {
+    fn insert(&mut self, module: &'a ActiveModule) {
+        let mut category = self;
+        for segment in module.info.categories() {
+            let index = match category
+                .children
+                .iter()
+                .position(|(name, _)| *name == segment)
+            {
+                Some(index) => index,
+                None => {
+                    category.children.push((segment, default()));
+                    category.children.len() - 1
+                }
+            };
+            category = &mut category.children[index].1;
+        }
+        category.modules.push(module);
+    }
+}
+
+#[cfg(feature = "enabled")]
+/// Draws the entries of the main window, keeping their layout up to date
+struct ModuleEntries<'a> {
+    modules: &'a mut BTreeMap<String, ModuleLayout>,
+    open_categories: &'a mut BTreeSet<String>,
+    favorites: &'a mut BTreeSet<String>,
+    render_module: &'a mut dyn FnMut(&mut egui::Ui, &ActiveModule),
+}
+
+#[cfg(feature = "enabled")]
+impl ModuleEntries<'_> {
+    fn category(&mut self, ui: &mut egui::Ui, category: &Category, path: &str) {
+        for (name, child) in &category.children {
+            let child_path = if path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{path}/{name}")
+            };
+            let response = egui::CollapsingHeader::new(*name)
+                .id_source(("buzzdebug_category", &child_path))
+                .default_open(self.open_categories.contains(&child_path))
+                .show(ui, |ui| self.category(ui, child, &child_path));
+            if response.body_response.is_some() {
+                self.open_categories.insert(child_path);
+            } else {
+                self.open_categories.remove(&child_path);
+            }
+        }
+
+        for module in &category.modules {
+            self.module(ui, module, module.info.name());
+        }
+    }
+
+    fn module(&mut self, ui: &mut egui::Ui, module: &ActiveModule, label: &str) {
+        let path = &module.info.path;
+        let module_layout = self.modules.entry(path.clone()).or_default();
+        if module_layout.detached {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                if ui.small_button("Attach").clicked() {
+                    module_layout.detached = false;
+                }
+                enabled_checkbox(ui, module_layout);
+                if module.has_gizmos() {
+                    ui.checkbox(&mut module_layout.gizmos, "Gizmos");
+                }
+            });
+            return;
+        }
+
+        let favorite = self.favorites.contains(path);
+        let mut toggle_favorite = false;
+        let response = egui::CollapsingHeader::new(label)
+            .id_source(("buzzdebug_module", path))
+            .default_open(module_layout.open)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.small_button("Detach").clicked() {
+                        module_layout.detached = true;
+                    }
+                    let favorite_label = if favorite { "Unfavorite" } else { "Favorite" };
+                    toggle_favorite = ui.small_button(favorite_label).clicked();
+                    enabled_checkbox(ui, module_layout);
+                    if module.has_gizmos() {
+                        ui.checkbox(&mut module_layout.gizmos, "Gizmos");
+                    }
+                });
+                (self.render_module)(ui, module);
+            });
+        module_layout.open = response.body_response.is_some();
+
+        if toggle_favorite && !self.favorites.remove(path) {
+            self.favorites.insert(path.clone());
+        }
+    }
+}
+
+#[cfg(feature = "enabled")]
+fn enabled_checkbox(ui: &mut egui::Ui, module_layout: &mut ModuleLayout) {
+    let mut enabled = !module_layout.disabled;
+    if ui.checkbox(&mut enabled, "Enabled").changed() {
+        module_layout.disabled = !enabled;
+    }
+}
+
+#[cfg(feature = "enabled")]
+/// Window restored from `layout`, which is updated with the window's current state
+fn layout_window(
+    ctx: &egui::Context,
+    title: &str,
+    id: egui::Id,
+    layout: &mut WindowLayout,
+    open: Option<&mut bool>,
+    add_contents: impl FnOnce(&mut egui::Ui),
+) {
+    let mut window = egui::Window::new(title)
+        .id(id)
+        .default_open(!layout.collapsed);
+    if let Some([x, y]) = layout.position {
+        window = window.default_pos(egui::pos2(x, y));
+    }
+    if let Some(open) = open {
+        window = window.open(open);
+    }
+
+    if let Some(response) = window.show(ctx, add_contents) {
+        let position = response.response.rect.min;
+        layout.position = Some([position.x, position.y]);
+        layout.collapsed = response.inner.is_none();
+    }
+}
+
+#[cfg(feature = "enabled")]
+fn staleness_label(ui: &mut egui::Ui, staleness: Option<Duration>) {
+    match staleness {
+        None => {
+            ui.weak("Waiting for server data...");
+        }
+        Some(staleness) if staleness > STALE_AFTER => {
+            ui.colored_label(
+                egui::Color32::RED,
+                format!("Stale: updated {:.1}s ago", staleness.as_secs_f32()),
+            );
+        }
+        Some(staleness) => {
+            ui.weak(format!("Updated {:.1}s ago", staleness.as_secs_f32()));
+        }
+    }
 }
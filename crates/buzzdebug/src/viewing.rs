@@ -0,0 +1,158 @@
+//! Modules only update their data while someone looks at it. The client tracks which modules it
+//! shows, and tells the server which remote ones those are so that the server only runs the update
+//! systems of remote modules that some client is viewing. Remote modules are identified by the
+//! type name of their data resource, which is part of the protocol, rather than by their path,
+//! which each peer chooses.
+//!
+//! Each module can also be disabled from the debug window, which stops its update systems even
+//! while it's viewed. Modules whose
+//! [`sample_always`](crate::plugin::LocalDebugModule::sample_always) is set keep sampling while
+//! they're enabled, viewed or not.
+
+#[cfg(feature = "enabled")]
+use bevy::ecs::component::Components;
+#[cfg(feature = "enabled")]
+use bevy::{
+    app::{FixedPostUpdate, FixedPreUpdate},
+    utils::HashMap,
+};
+use bevy::{ecs::component::ComponentId, prelude::*, utils::HashSet};
+#[cfg(feature = "enabled")]
+use lightyear::prelude::{
+    client::{self, ConnectEvent},
+    server::{self, DisconnectEvent},
+    ClientId,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "enabled")]
+use super::{
+    commands::DebugCommandChannel,
+    plugin::{DebugMode, DebugModules},
+};
+
+/// Update systems of the module with this data resource. It only runs while the module is enabled
+/// and viewed (or only enabled, for modules that sample always), in every schedule of [`Main`]
+/// from [`First`] to [`Last`], including the fixed ones.
+#[derive(SystemSet, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct DebugModuleSystems(pub ComponentId);
+
+/// Data resources of the modules whose update systems run: on the client the ones it views, on
+/// the server the remote ones any client views
+#[derive(Resource, Clone, Default, PartialEq, Debug)]
+pub struct EnabledDebugModules(pub HashSet<ComponentId>);
+
+impl EnabledDebugModules {
+    pub fn contains(&self, data: ComponentId) -> bool {
+        self.0.contains(&data)
+    }
+}
+
+/// Type names of the data resources of the remote modules a client is viewing, sent every time
+/// they change
+// Without the UI, only registered so that the protocol stays the same
+#[cfg_attr(not(feature = "enabled"), allow(dead_code))]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub(crate) struct ViewedModules(pub Vec<String>);
+
+#[cfg(feature = "enabled")]
+/// Remote modules viewed by each client, on the server
+#[derive(Resource, Default)]
+pub(crate) struct DebugModuleViewers {
+    /// Data resource of each remote module, by type name
+    pub data: HashMap<String, ComponentId>,
+    viewed: HashMap<ClientId, Vec<String>>,
+    /// Debug observers connected to the server (see [`crate::observer`]), which view every module
+    pub observers: usize,
+    /// Every module is enabled, e.g. to log snapshots
+    pub always: bool,
+}
+
+/// Identifies the data resource `data` across peers, whose `ComponentId`s differ
+#[cfg(feature = "enabled")]
+pub(crate) fn data_name(components: &Components, data: ComponentId) -> Option<String> {
+    components
+        .get_info(data)
+        .map(|info| info.name().to_string())
+}
+
+#[cfg(feature = "enabled")]
+impl DebugModuleViewers {
+    pub fn enabled(&self) -> EnabledDebugModules {
+        if self.always || self.observers > 0 {
+            return EnabledDebugModules(self.data.values().copied().collect());
+        }
+        EnabledDebugModules(
+            self.viewed
+                .values()
+                .flatten()
+                .filter_map(|name| self.data.get(name).copied())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn configure_module_systems(app: &mut App, data: ComponentId) {
+    let set = DebugModuleSystems(data);
+    let enabled = move |enabled: Res<EnabledDebugModules>| enabled.contains(data);
+
+    app.configure_sets(First, set.run_if(enabled));
+    app.configure_sets(PreUpdate, set.run_if(enabled));
+    app.configure_sets(FixedPreUpdate, set.run_if(enabled));
+    app.configure_sets(FixedUpdate, set.run_if(enabled));
+    app.configure_sets(FixedPostUpdate, set.run_if(enabled));
+    app.configure_sets(Update, set.run_if(enabled));
+    app.configure_sets(PostUpdate, set.run_if(enabled));
+    app.configure_sets(Last, set.run_if(enabled));
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn send_viewed_modules(
+    enabled: Res<EnabledDebugModules>,
+    debug_modules: Res<DebugModules>,
+    components: &Components,
+    mut connect_events: EventReader<ConnectEvent>,
+    mut connection: ResMut<client::ConnectionManager>,
+) {
+    // The server forgets about clients when they disconnect
+    let reconnected = connect_events.read().count() > 0;
+    if !enabled.is_changed() && !reconnected {
+        return;
+    }
+
+    let viewed = debug_modules
+        .modules
+        .iter()
+        .filter(|module| matches!(module.mode, DebugMode::Server(_)))
+        .filter(|module| enabled.contains(module.data))
+        .filter_map(|module| data_name(components, module.data))
+        .collect();
+    if let Err(e) = connection.send_message::<DebugCommandChannel, _>(&ViewedModules(viewed)) {
+        error!("Could not send the viewed debug modules to the server: {e:?}");
+    }
+}
+
+#[cfg(feature = "enabled")]
+pub(crate) fn receive_viewed_modules(
+    mut messages: EventReader<server::MessageEvent<ViewedModules>>,
+    mut disconnect_events: EventReader<DisconnectEvent>,
+    mut viewers: ResMut<DebugModuleViewers>,
+    mut enabled: ResMut<EnabledDebugModules>,
+) {
+    let mut changed = false;
+    for message in messages.read() {
+        viewers
+            .viewed
+            .insert(*message.context(), message.message().0.clone());
+        changed = true;
+    }
+    for event in disconnect_events.read() {
+        changed |= viewers.viewed.remove(&event.client_id).is_some();
+    }
+    if !changed {
+        return;
+    }
+
+    enabled.set_if_neq(viewers.enabled());
+}
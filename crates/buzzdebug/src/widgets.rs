@@ -0,0 +1,81 @@
+//! Small UI helpers shared by the builtin modules
+
+use std::collections::VecDeque;
+
+#[cfg(feature = "enabled")]
+use bevy_inspector_egui::egui;
+use serde::{Deserialize, Serialize};
+
+/// Fixed-size window of the latest values of a measurement. Serializable so that remote modules
+/// can replicate it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct History {
+    values: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            values: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, value: f32) {
+        if self.values.len() == self.capacity {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    pub fn latest(&self) -> Option<f32> {
+        self.values.back().copied()
+    }
+
+    pub fn values(&self) -> impl ExactSizeIterator<Item = f32> + Clone + '_ {
+        self.values.iter().copied()
+    }
+
+    pub fn max(&self) -> f32 {
+        self.values().fold(0., f32::max)
+    }
+}
+
+#[cfg(feature = "enabled")]
+/// Line graph of `values`, scaled so that the largest one reaches the top
+pub fn sparkline(ui: &mut egui::Ui, values: impl ExactSizeIterator<Item = f32> + Clone) {
+    let desired_size = egui::vec2(ui.available_width(), 32.);
+    let (rect, _) = ui.allocate_exact_size(desired_size, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2., ui.visuals().extreme_bg_color);
+
+    let n = values.len();
+    if n < 2 {
+        return;
+    }
+    let max = values.clone().fold(f32::EPSILON, f32::max);
+
+    let points = values
+        .enumerate()
+        .map(|(i, value)| {
+            let x = rect.left() + rect.width() * i as f32 / (n - 1) as f32;
+            let y = rect.bottom() - rect.height() * (value / max).clamp(0., 1.);
+            egui::pos2(x, y)
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1., ui.visuals().text_color()),
+    ));
+}
+
+#[cfg(feature = "enabled")]
+/// Label with the latest value of a history, followed by its graph
+pub fn history_row(ui: &mut egui::Ui, label: &str, history: &History, unit: &str) {
+    match history.latest() {
+        Some(latest) => ui.label(format!("{label}: {latest:.1} {unit}")),
+        None => ui.label(format!("{label}: -")),
+    };
+    sparkline(ui, history.values());
+}
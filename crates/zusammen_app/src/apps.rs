@@ -9,9 +9,12 @@ use lightyear::prelude::*;
 use lightyear::prelude::{client, server};
 use lightyear::server::config::ServerConfig;
 use lightyear::transport::LOCAL_SOCKET;
-use std::net::{Ipv4Addr, SocketAddr};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::time::Duration;
 
+use crate::protocol::GameInfo;
+
 const PROTOCOL_ID: u64 = 0;
 const PRIVATE_KEY: [u8; PRIVATE_KEY_BYTES] = [
     12, 58, 98, 88, 72, 254, 12, 121, 99, 83, 211, 132, 199, 12, 58, 98, 88, 72, 254, 12, 121, 99,
@@ -39,23 +42,119 @@ pub struct NetParameters {
 #[derive(Clone, Debug)]
 pub struct SharedParams {
     pub tick_duration: Duration,
+    pub game: GameInfo,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct ClientParams {
     pub transport: ClientTransportParams,
     /// Maximum number of input delay ticks before prediction starts
     pub maximum_input_delay_ticks: u16,
     pub correction_ticks_factor: f32,
+    pub link_conditioner: Option<LinkConditionerParams>,
+    /// Sent to the server when joining, see [`AdmissionParams::password`]
+    pub password: Option<String>,
 }
 
-#[derive(Clone, Debug)]
+impl Default for ClientParams {
+    fn default() -> Self {
+        Self {
+            transport: default(),
+            maximum_input_delay_ticks: 0,
+            // Same as lightyear's default, 0 would disable the correction
+            correction_ticks_factor: 1.,
+            link_conditioner: None,
+            password: None,
+        }
+    }
+}
+
+impl fmt::Debug for ClientParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientParams")
+            .field("transport", &self.transport)
+            .field("maximum_input_delay_ticks", &self.maximum_input_delay_ticks)
+            .field("correction_ticks_factor", &self.correction_ticks_factor)
+            .field("link_conditioner", &self.link_conditioner)
+            .field("password", &redacted(&self.password))
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub struct ServerParams {
     pub port: u16,
-    pub local_channel: Option<(Receiver<Vec<u8>>, Sender<Vec<u8>>)>,
+    /// In-process clients, each one connected through its own pair of channels
+    pub local_channels: Vec<(Receiver<Vec<u8>>, Sender<Vec<u8>>)>,
+    pub link_conditioner: Option<LinkConditionerParams>,
+    pub admission: AdmissionParams,
 }
 
-#[derive(Clone, Debug)]
+/// Which clients the server lets in. Clients that don't pass are told why and disconnected.
+#[derive(Resource, Clone, Default)]
+pub struct AdmissionParams {
+    /// `None` for no limit
+    pub max_clients: Option<usize>,
+    pub address_filter: AddressFilter,
+    /// Password clients have to send when joining, `None` to accept any client
+    pub password: Option<String>,
+}
+
+impl fmt::Debug for AdmissionParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdmissionParams")
+            .field("max_clients", &self.max_clients)
+            .field("address_filter", &self.address_filter)
+            .field("password", &redacted(&self.password))
+            .finish()
+    }
+}
+
+/// Passwords are left out of the params when they are printed, e.g. in logs
+fn redacted(password: &Option<String>) -> Option<&'static str> {
+    password.as_ref().map(|_| "<redacted>")
+}
+
+#[derive(Clone, Debug, Default)]
+pub enum AddressFilter {
+    #[default]
+    AllowAll,
+    /// Only clients with one of these IP addresses are accepted
+    Allow(Vec<IpAddr>),
+    /// Clients with any of these IP addresses are rejected
+    Deny(Vec<IpAddr>),
+}
+
+impl AddressFilter {
+    pub fn accepts(&self, ip: IpAddr) -> bool {
+        match self {
+            AddressFilter::AllowAll => true,
+            AddressFilter::Allow(allowed) => allowed.contains(&ip),
+            AddressFilter::Deny(denied) => !denied.contains(&ip),
+        }
+    }
+}
+
+/// Simulated network conditions, applied to the packets received by the side that sets them
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinkConditionerParams {
+    pub latency: Duration,
+    pub jitter: Duration,
+    /// Fraction of packets that are dropped, between 0 and 1
+    pub packet_loss: f32,
+}
+
+impl LinkConditionerParams {
+    fn to_config(self) -> LinkConditionerConfig {
+        LinkConditionerConfig {
+            incoming_latency: self.latency,
+            incoming_jitter: self.jitter,
+            incoming_loss: self.packet_loss,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub enum ClientTransportParams {
     UdpSocket {
         server_addr: SocketAddr,
@@ -65,6 +164,7 @@ pub enum ClientTransportParams {
         send: Sender<Vec<u8>>,
     },
     /// e.g. while in main menu, configuring connection
+    #[default]
     None,
 }
 
@@ -86,7 +186,10 @@ pub fn make_client_config(
         ClientTransportParams::None => (ClientTransport::Dummy, LOCAL_SOCKET),
     };
 
-    let io_config = client::IoConfig::from_transport(transport);
+    let mut io_config = client::IoConfig::from_transport(transport);
+    if let Some(conditioner) = client_params.link_conditioner {
+        io_config = io_config.with_conditioner(conditioner.to_config());
+    }
 
     let auth = client::Authentication::Manual {
         server_addr,
@@ -120,10 +223,16 @@ pub fn make_server_config(
     shared_params: &SharedParams,
 ) -> ServerConfig {
     let mut extra_transport_configs = vec![];
-    if let Some((recv, send)) = server_params.local_channel.clone() {
-        extra_transport_configs.push(server::ServerTransport::Channels {
-            channels: vec![(LOCAL_SOCKET, recv, send)],
-        });
+    if !server_params.local_channels.is_empty() {
+        // Each local client needs a distinct address so that netcode can tell them apart
+        let channels = server_params
+            .local_channels
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, (recv, send))| (local_channel_addr(i), recv, send))
+            .collect();
+        extra_transport_configs.push(server::ServerTransport::Channels { channels });
     }
 
     // configure the network configuration
@@ -132,11 +241,12 @@ pub fn make_server_config(
             Ipv4Addr::UNSPECIFIED.into(),
             server_params.port,
         )),
+        server_params.link_conditioner,
     )];
 
     let extra_net_configs = extra_transport_configs
         .into_iter()
-        .map(|c| build_server_netcode_config(c));
+        .map(|c| build_server_netcode_config(c, server_params.link_conditioner));
     net_configs.extend(extra_net_configs);
 
     let server_config = ServerConfig {
@@ -152,13 +262,26 @@ pub fn make_server_config(
     server_config
 }
 
-fn build_server_netcode_config(transport_config: server::ServerTransport) -> server::NetConfig {
+/// Address the server sees for the `i`th in-process client
+fn local_channel_addr(i: usize) -> SocketAddr {
+    let port = u16::try_from(i)
+        .ok()
+        .and_then(|i| LOCAL_SOCKET.port().checked_add(i))
+        .unwrap_or_else(|| panic!("Too many in-process clients, no port left for client {i}"));
+    SocketAddr::new(LOCAL_SOCKET.ip(), port)
+}
+
+fn build_server_netcode_config(
+    transport_config: server::ServerTransport,
+    link_conditioner: Option<LinkConditionerParams>,
+) -> server::NetConfig {
     let netcode_config = server::NetcodeConfig::default()
         .with_protocol_id(PROTOCOL_ID)
         .with_key(PRIVATE_KEY);
 
     let io_config = server::IoConfig {
         transport: transport_config,
+        conditioner: link_conditioner.map(LinkConditionerParams::to_config),
         ..default()
     };
 
@@ -167,3 +290,57 @@ fn build_server_netcode_config(transport_config: server::ServerTransport) -> ser
         io: io_config,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const OTHER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 2));
+
+    #[test]
+    fn allow_all_accepts_any_address() {
+        assert!(AddressFilter::AllowAll.accepts(LOCALHOST));
+        assert!(AddressFilter::AllowAll.accepts(OTHER));
+    }
+
+    #[test]
+    fn empty_allow_list_accepts_nothing() {
+        assert!(!AddressFilter::Allow(vec![]).accepts(LOCALHOST));
+    }
+
+    #[test]
+    fn allow_list_only_accepts_its_addresses() {
+        let filter = AddressFilter::Allow(vec![LOCALHOST]);
+        assert!(filter.accepts(LOCALHOST));
+        assert!(!filter.accepts(OTHER));
+        // The same host over IPv6 is a different address
+        assert!(!filter.accepts(IpAddr::V6(std::net::Ipv6Addr::LOCALHOST)));
+    }
+
+    #[test]
+    fn empty_deny_list_accepts_everything() {
+        assert!(AddressFilter::Deny(vec![]).accepts(OTHER));
+    }
+
+    #[test]
+    fn deny_list_rejects_its_addresses() {
+        let filter = AddressFilter::Deny(vec![OTHER]);
+        assert!(filter.accepts(LOCALHOST));
+        assert!(!filter.accepts(OTHER));
+    }
+
+    #[test]
+    fn passwords_are_not_printed() {
+        let admission = AdmissionParams {
+            password: Some("hunter2".to_string()),
+            ..default()
+        };
+        let client = ClientParams {
+            password: Some("hunter2".to_string()),
+            ..default()
+        };
+        assert!(!format!("{admission:?}").contains("hunter2"));
+        assert!(!format!("{client:?}").contains("hunter2"));
+    }
+}
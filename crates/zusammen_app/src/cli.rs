@@ -0,0 +1,132 @@
+//! Command line arguments for settings that are handy to change without recompiling.
+//!
+//! Meant to be merged into the game's own [`clap::Command`]:
+//!
+//! ```ignore
+//! let matches = Command::new("game").args(zusammen_app::cli_args()).get_matches();
+//! let config = config.with_cli_args(&matches);
+//! ```
+
+use std::time::Duration;
+
+use clap::{value_parser, Arg, ArgMatches};
+
+use crate::{apps::LinkConditionerParams, core::ZusammenAppConfig};
+
+/// Argument names for the settings of one side of the connection
+struct RoleArgs {
+    role: &'static str,
+    latency: &'static str,
+    jitter: &'static str,
+    packet_loss: &'static str,
+}
+
+const CLIENT_ARGS: RoleArgs = RoleArgs {
+    role: "client",
+    latency: "client-latency-ms",
+    jitter: "client-jitter-ms",
+    packet_loss: "client-packet-loss",
+};
+
+const SERVER_ARGS: RoleArgs = RoleArgs {
+    role: "server",
+    latency: "server-latency-ms",
+    jitter: "server-jitter-ms",
+    packet_loss: "server-packet-loss",
+};
+
+pub fn cli_args() -> Vec<Arg> {
+    [CLIENT_ARGS, SERVER_ARGS]
+        .into_iter()
+        .flat_map(|args| {
+            let role = args.role;
+            [
+                Arg::new(args.latency)
+                    .long(args.latency)
+                    .value_parser(value_parser!(u64))
+                    .help(format!(
+                        "Simulated latency of packets received by the {role}"
+                    )),
+                Arg::new(args.jitter)
+                    .long(args.jitter)
+                    .value_parser(value_parser!(u64))
+                    .help(format!(
+                        "Simulated jitter of packets received by the {role}"
+                    )),
+                Arg::new(args.packet_loss)
+                    .long(args.packet_loss)
+                    .value_parser(parse_packet_loss)
+                    .help(format!(
+                        "Fraction (0 to 1) of packets received by the {role} that are dropped"
+                    )),
+            ]
+        })
+        .collect()
+}
+
+fn parse_packet_loss(value: &str) -> Result<f32, String> {
+    let packet_loss: f32 = value.parse().map_err(|e| format!("{e}"))?;
+    if !(0.0..=1.0).contains(&packet_loss) {
+        return Err(format!("{packet_loss} is not between 0 and 1"));
+    }
+    Ok(packet_loss)
+}
+
+/// Reads the link conditioner of a role, `None` if none of its arguments were given
+fn link_conditioner_from_matches(
+    matches: &ArgMatches,
+    args: &RoleArgs,
+) -> Option<LinkConditionerParams> {
+    let latency = matches.get_one::<u64>(args.latency).copied();
+    let jitter = matches.get_one::<u64>(args.jitter).copied();
+    let packet_loss = matches.get_one::<f32>(args.packet_loss).copied();
+
+    if latency.is_none() && jitter.is_none() && packet_loss.is_none() {
+        return None;
+    }
+
+    Some(LinkConditionerParams {
+        latency: Duration::from_millis(latency.unwrap_or(0)),
+        jitter: Duration::from_millis(jitter.unwrap_or(0)),
+        packet_loss: packet_loss.unwrap_or(0.),
+    })
+}
+
+impl ZusammenAppConfig {
+    /// Overrides the settings given on the command line, see [`cli_args`]
+    pub fn with_cli_args(mut self, matches: &ArgMatches) -> Self {
+        if let Some(conditioner) = link_conditioner_from_matches(matches, &CLIENT_ARGS) {
+            self.client_link_conditioner = Some(conditioner);
+        }
+        if let Some(conditioner) = link_conditioner_from_matches(matches, &SERVER_ARGS) {
+            self.server_link_conditioner = Some(conditioner);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_loss_accepts_the_bounds() {
+        assert_eq!(parse_packet_loss("0"), Ok(0.));
+        assert_eq!(parse_packet_loss("0.25"), Ok(0.25));
+        assert_eq!(parse_packet_loss("1"), Ok(1.));
+    }
+
+    #[test]
+    fn packet_loss_rejects_out_of_range_values() {
+        assert!(parse_packet_loss("-0.01").is_err());
+        assert!(parse_packet_loss("1.01").is_err());
+        assert!(parse_packet_loss("NaN").is_err());
+        assert!(parse_packet_loss("inf").is_err());
+    }
+
+    #[test]
+    fn packet_loss_rejects_non_numbers() {
+        assert!(parse_packet_loss("").is_err());
+        assert!(parse_packet_loss("10%").is_err());
+    }
+}
@@ -1,14 +1,125 @@
 use std::sync::Arc;
 
 use bevy::prelude::*;
+use lightyear::{
+    client::config::ClientConfig,
+    prelude::client::{ConnectEvent, ConnectionManager, DisconnectEvent, MessageEvent},
+};
 use zusammen_plugin::ZusammenPlugin;
 
+use crate::protocol::{
+    GameInfo, JoinRequest, JoinResponse, ProtocolFingerprint, ZusammenChannel,
+    ZusammenConnectionError,
+};
+
+/// Live-editable prediction settings of the client.
+///
+/// Lightyear reads these from the [`ClientConfig`] every frame, so changes take effect without
+/// having to reconnect.
+#[derive(Resource, Clone, Debug, PartialEq)]
+pub struct ClientPredictionSettings {
+    /// Maximum number of input delay ticks before prediction starts
+    pub maximum_input_delay_ticks: u16,
+    pub correction_ticks_factor: f32,
+}
+
+impl FromWorld for ClientPredictionSettings {
+    fn from_world(world: &mut World) -> Self {
+        let prediction = &world.resource::<ClientConfig>().prediction;
+        Self {
+            maximum_input_delay_ticks: prediction.maximum_input_delay_before_prediction,
+            correction_ticks_factor: prediction.correction_ticks_factor,
+        }
+    }
+}
+
+/// Outcome of the [`JoinRequest`] sent when connecting, exposed through the
+/// [`ClientZusammenAppManager`](crate::ClientZusammenAppManager)
+#[derive(Resource, Default)]
+pub struct ClientJoinState {
+    pub(crate) password: Option<String>,
+    pub(crate) admitted: bool,
+    pub(crate) error: Option<ZusammenConnectionError>,
+}
+
 pub struct ClientPlugin {
     pub zusammen: Arc<dyn ZusammenPlugin + Send + Sync + 'static>,
+    pub password: Option<String>,
 }
 
 impl Plugin for ClientPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<ClientPredictionSettings>();
+        app.insert_resource(ClientJoinState {
+            password: self.password.clone(),
+            ..default()
+        });
+        app.add_systems(
+            PreUpdate,
+            apply_prediction_settings.run_if(resource_changed::<ClientPredictionSettings>),
+        );
+        app.add_systems(
+            Update,
+            (send_join_request, handle_join_response, forget_admission),
+        );
+
         self.zusammen.add_client(app);
     }
 }
+
+fn apply_prediction_settings(
+    settings: Res<ClientPredictionSettings>,
+    mut client_config: ResMut<ClientConfig>,
+) {
+    client_config
+        .prediction
+        .maximum_input_delay_before_prediction = settings.maximum_input_delay_ticks;
+    client_config.prediction.correction_ticks_factor = settings.correction_ticks_factor;
+}
+
+fn send_join_request(
+    mut connections: EventReader<ConnectEvent>,
+    mut connection: ResMut<ConnectionManager>,
+    mut join_state: ResMut<ClientJoinState>,
+    game: Res<GameInfo>,
+    fingerprint: Res<ProtocolFingerprint>,
+) {
+    for _ in connections.read() {
+        join_state.admitted = false;
+        join_state.error = None;
+
+        let request = JoinRequest {
+            game: game.clone(),
+            protocol_fingerprint: fingerprint.0,
+            password: join_state.password.clone(),
+        };
+        if let Err(e) = connection.send_message::<ZusammenChannel, _>(&request) {
+            error!("Could not send join request: {e:?}");
+        }
+    }
+}
+
+fn handle_join_response(
+    mut responses: EventReader<MessageEvent<JoinResponse>>,
+    mut join_state: ResMut<ClientJoinState>,
+) {
+    for response in responses.read() {
+        match response.message() {
+            JoinResponse::Accepted => join_state.admitted = true,
+            JoinResponse::Rejected(reason) => {
+                warn!("The server rejected this client: {reason}");
+                join_state.error = Some(reason.clone());
+            }
+        }
+    }
+}
+
+/// The server has to admit the client again after reconnecting
+fn forget_admission(
+    mut disconnections: EventReader<DisconnectEvent>,
+    mut join_state: ResMut<ClientJoinState>,
+) {
+    if disconnections.read().count() > 0 {
+        join_state.admitted = false;
+    }
+}
@@ -30,8 +30,12 @@ use crate::{
 };
 
 use super::{
-    apps::{make_client_config, ClientParams, ClientTransportParams, ServerParams, SharedParams},
-    client::ClientPlugin,
+    apps::{
+        make_client_config, AdmissionParams, ClientParams, ClientTransportParams,
+        LinkConditionerParams, ServerParams, SharedParams,
+    },
+    client::{ClientJoinState, ClientPlugin, ClientPredictionSettings},
+    protocol::{GameInfo, ZusammenConnectionError},
     shared::SharedPlugin,
 };
 
@@ -112,6 +116,72 @@ fn server_app<M, N>(
     app
 }
 
+/// Windowless and renderless plugins, enough to run the networking side of a [`ZusammenPlugin`]
+fn headless_plugins(app: &mut App) {
+    app.add_plugins(MinimalPlugins);
+    app.add_plugins(StatesPlugin);
+    app.add_plugins(AssetPlugin::default());
+    app.add_plugins(HierarchyPlugin);
+    app.add_plugins(TransformPlugin::default());
+}
+
+/// Builds a server app without any rendering or windowing, e.g. for tests. The app is not run,
+/// the caller is expected to drive it with [`App::update`].
+pub fn headless_server_app(
+    server_params: &ServerParams,
+    shared_params: &SharedParams,
+    zusammen: Arc<dyn ZusammenPlugin + Send + Sync + 'static>,
+) -> App {
+    let mut app = App::new();
+    headless_plugins(&mut app);
+
+    app.add_plugins(ServerPlugins {
+        config: make_server_config(server_params, shared_params),
+    });
+
+    app.add_plugins(ServerPlugin {
+        zusammen: zusammen.clone(),
+        admission: server_params.admission.clone(),
+    })
+    .add_plugins(SharedPlugin {
+        zusammen,
+        game: shared_params.game.clone(),
+    });
+
+    app
+}
+
+/// Builds a client app without any rendering or windowing, e.g. for tests. The app is not run,
+/// the caller is expected to drive it with [`App::update`].
+pub fn headless_client_app(
+    client_params: &ClientParams,
+    shared_params: &SharedParams,
+    config: ZusammenAppConfig,
+) -> App {
+    let mut app = App::new();
+    headless_plugins(&mut app);
+
+    app.insert_resource(ClientZusammenAppStuff {
+        tx: None,
+        config: config.clone(),
+    });
+
+    app.add_plugins(lightyear::prelude::client::ClientPlugins {
+        config: make_client_config(client_params, shared_params),
+    });
+
+    app.add_plugins(SharedPlugin {
+        zusammen: config.plugin.clone(),
+        game: shared_params.game.clone(),
+    })
+    .add_plugins(ClientPlugin {
+        zusammen: config.plugin,
+        password: client_params.password.clone(),
+    });
+
+    app
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ZusammenAppMode {
     Server { port: u16 },
@@ -120,10 +190,41 @@ pub enum ZusammenAppMode {
     Lobby,
 }
 
+/// How [`run_multiplayer_app`] runs a [`ZusammenPlugin`]. [`ZusammenAppConfig::new`] only takes
+/// the plugin, the mode and the game, and leaves the other fields at their defaults: no link
+/// conditioners, [`AdmissionParams::default`] and no password.
 #[derive(Clone)]
 pub struct ZusammenAppConfig {
     pub plugin: Arc<dyn ZusammenPlugin + Send + Sync + 'static>,
     pub mode: ZusammenAppMode,
+    /// Simulated network conditions for packets received by the client
+    pub client_link_conditioner: Option<LinkConditionerParams>,
+    /// Simulated network conditions for packets received by the server
+    pub server_link_conditioner: Option<LinkConditionerParams>,
+    /// Which clients the server accepts, used in Server and Host modes
+    pub admission: AdmissionParams,
+    /// Password sent to the server in Client mode
+    pub password: Option<String>,
+    pub game: GameInfo,
+}
+
+impl ZusammenAppConfig {
+    /// No link conditioners, no admission limits and no password
+    pub fn new(
+        plugin: Arc<dyn ZusammenPlugin + Send + Sync + 'static>,
+        mode: ZusammenAppMode,
+        game: GameInfo,
+    ) -> Self {
+        Self {
+            plugin,
+            mode,
+            client_link_conditioner: None,
+            server_link_conditioner: None,
+            admission: default(),
+            password: None,
+            game,
+        }
+    }
 }
 
 pub fn run_multiplayer_app(config: ZusammenAppConfig, input_delay: u16, correction_factor: f32) {
@@ -131,10 +232,13 @@ pub fn run_multiplayer_app(config: ZusammenAppConfig, input_delay: u16, correcti
         ZusammenAppMode::Server { port } => {
             let shared_params = SharedParams {
                 tick_duration: Duration::from_secs_f64(1. / 64.),
+                game: config.game.clone(),
             };
             let server_params = ServerParams {
                 port,
-                local_channel: None,
+                local_channels: vec![],
+                link_conditioner: config.server_link_conditioner,
+                admission: config.admission.clone(),
             };
 
             let server_config = make_server_config(&server_params, &shared_params);
@@ -142,9 +246,11 @@ pub fn run_multiplayer_app(config: ZusammenAppConfig, input_delay: u16, correcti
                 server_config,
                 ServerPlugin {
                     zusammen: config.plugin.clone(),
+                    admission: server_params.admission.clone(),
                 },
                 SharedPlugin {
                     zusammen: config.plugin.clone(),
+                    game: config.game.clone(),
                 },
                 true,
             );
@@ -158,9 +264,12 @@ pub fn run_multiplayer_app(config: ZusammenAppConfig, input_delay: u16, correcti
                 },
                 maximum_input_delay_ticks: input_delay,
                 correction_ticks_factor: correction_factor,
+                link_conditioner: config.client_link_conditioner,
+                password: config.password.clone(),
             };
             let shared_params = SharedParams {
                 tick_duration: Duration::from_secs_f64(1. / 64.),
+                game: config.game.clone(),
             };
 
             let client_config = make_client_config(&client_params, &shared_params);
@@ -172,9 +281,11 @@ pub fn run_multiplayer_app(config: ZusammenAppConfig, input_delay: u16, correcti
                 },
                 ClientPlugin {
                     zusammen: config.plugin.clone(),
+                    password: client_params.password.clone(),
                 },
                 SharedPlugin {
                     zusammen: config.plugin.clone(),
+                    game: config.game.clone(),
                 },
             );
             app.run();
@@ -195,13 +306,18 @@ pub fn run_multiplayer_app(config: ZusammenAppConfig, input_delay: u16, correcti
                 },
                 maximum_input_delay_ticks: input_delay,
                 correction_ticks_factor: correction_factor,
+                link_conditioner: config.client_link_conditioner,
+                password: config.admission.password.clone(),
             };
             let shared_params = SharedParams {
                 tick_duration: Duration::from_secs_f64(1. / 64.),
+                game: config.game.clone(),
             };
             let server_params = ServerParams {
                 port,
-                local_channel: Some((to_server_recv, from_server_send)),
+                local_channels: vec![(to_server_recv, from_server_send)],
+                link_conditioner: config.server_link_conditioner,
+                admission: config.admission.clone(),
             };
 
             let client_config = make_client_config(&client_params, &shared_params);
@@ -213,9 +329,11 @@ pub fn run_multiplayer_app(config: ZusammenAppConfig, input_delay: u16, correcti
                 },
                 ClientPlugin {
                     zusammen: config.plugin.clone(),
+                    password: client_params.password.clone(),
                 },
                 SharedPlugin {
                     zusammen: config.plugin.clone(),
+                    game: config.game.clone(),
                 },
             );
             let server_msg = ServerAppMessage {
@@ -236,9 +354,12 @@ pub fn run_multiplayer_app(config: ZusammenAppConfig, input_delay: u16, correcti
                 transport: ClientTransportParams::None,
                 maximum_input_delay_ticks: input_delay,
                 correction_ticks_factor: correction_factor,
+                link_conditioner: config.client_link_conditioner,
+                password: None,
             };
             let shared_params = SharedParams {
                 tick_duration: Duration::from_secs_f64(1. / 64.),
+                game: config.game.clone(),
             };
 
             let client_config = make_client_config(&client_params, &shared_params);
@@ -250,9 +371,11 @@ pub fn run_multiplayer_app(config: ZusammenAppConfig, input_delay: u16, correcti
                 },
                 ClientPlugin {
                     zusammen: config.plugin.clone(),
+                    password: client_params.password.clone(),
                 },
                 SharedPlugin {
                     zusammen: config.plugin.clone(),
+                    game: config.game.clone(),
                 },
             );
             app.run();
@@ -274,9 +397,11 @@ fn server_thread(rx: Receiver<ServerAppMessage>) {
         server_config,
         ServerPlugin {
             zusammen: msg.zusammen.clone(),
+            admission: msg.server_params.admission.clone(),
         },
         SharedPlugin {
             zusammen: msg.zusammen,
+            game: msg.shared_params.game.clone(),
         },
         false,
     )
@@ -287,12 +412,20 @@ fn server_thread(rx: Receiver<ServerAppMessage>) {
 pub struct ClientZusammenAppManager<'w> {
     server_tx: ResMut<'w, ClientZusammenAppStuff>,
     client_config: ResMut<'w, ClientConfig>,
+    prediction_settings: ResMut<'w, ClientPredictionSettings>,
+    join_state: ResMut<'w, ClientJoinState>,
 }
 
 impl ClientZusammenAppManager<'_> {
     pub fn update_client_and_start_server(&mut self, params: NetParameters) {
         let new_client_config = make_client_config(&params.client, &params.shared);
         *self.client_config = new_client_config;
+        *self.prediction_settings = ClientPredictionSettings {
+            maximum_input_delay_ticks: params.client.maximum_input_delay_ticks,
+            correction_ticks_factor: params.client.correction_ticks_factor,
+        };
+        self.join_state.password = params.client.password.clone();
+        self.join_state.error = None;
 
         if let Some(server_params) = params.server.as_ref() {
             let server_msg = ServerAppMessage {
@@ -308,4 +441,14 @@ impl ClientZusammenAppManager<'_> {
                 .unwrap();
         }
     }
+
+    /// Whether the server accepted this client after connecting
+    pub fn is_admitted(&self) -> bool {
+        self.join_state.admitted
+    }
+
+    /// Why the server rejected this client, if it did
+    pub fn connection_error(&self) -> Option<&ZusammenConnectionError> {
+        self.join_state.error.as_ref()
+    }
 }
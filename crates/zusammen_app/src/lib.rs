@@ -1,9 +1,20 @@
 mod apps;
+mod cli;
 mod client;
 mod core;
 mod protocol;
 mod server;
 mod shared;
 
-pub use apps::{ClientParams, ClientTransportParams, NetParameters, ServerParams, SharedParams};
-pub use core::{run_multiplayer_app, ClientZusammenAppManager, ZusammenAppConfig, ZusammenAppMode};
+pub use apps::{
+    AddressFilter, AdmissionParams, ClientParams, ClientTransportParams, LinkConditionerParams,
+    NetParameters, ServerParams, SharedParams,
+};
+pub use cli::cli_args;
+pub use client::ClientPredictionSettings;
+pub use core::{
+    headless_client_app, headless_server_app, run_multiplayer_app, ClientZusammenAppManager,
+    ZusammenAppConfig, ZusammenAppMode,
+};
+pub use protocol::{GameInfo, ProtocolFingerprint, ZusammenConnectionError};
+pub use server::{client_ip, AdmittedClients, ClientAdmittedEvent};
@@ -1,14 +1,261 @@
-use std::sync::Arc;
+use std::{fmt, hash::Hasher, sync::Arc};
 
 use bevy::prelude::*;
+use lightyear::{
+    prelude::*,
+    protocol::{
+        channel::ChannelRegistry, component::ComponentRegistry, message::MessageRegistry,
+        registry::NetId,
+    },
+};
+use serde::{Deserialize, Serialize};
 use zusammen_plugin::ZusammenPlugin;
 
+/// Channel for zusammen_app's own messages, kept apart from the user protocol
+#[derive(Channel)]
+pub struct ZusammenChannel;
+
+/// Identifies the game that is being played, peers only accept each other if the names match
+/// and the versions are compatible (see [`GameInfo::is_compatible_with`])
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GameInfo {
+    pub name: String,
+    /// Semantic version, e.g. `env!("CARGO_PKG_VERSION")`
+    pub version: String,
+}
+
+impl GameInfo {
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+        }
+    }
+
+    /// Same major version, or same minor version for `0.x` versions. Versions that aren't
+    /// `major.minor.patch` must match exactly.
+    pub fn is_compatible_with(&self, other: &GameInfo) -> bool {
+        if self.name != other.name {
+            return false;
+        }
+        match (parse_version(&self.version), parse_version(&other.version)) {
+            (Some((0, minor, _)), Some((0, other_minor, _))) => minor == other_minor,
+            (Some((major, _, _)), Some((other_major, _, _))) => major == other_major,
+            _ => self.version == other.version,
+        }
+    }
+}
+
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    // Pre-release and build metadata don't matter for compatibility
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+    parts.next().is_none().then_some(version)
+}
+
+/// Fingerprint of the registered protocol: the channels, messages and components lightyear knows
+/// about, by kind and in the order of their network ids, which is what peers have to agree on.
+/// Plugins can add to it with [`ZusammenPlugin::protocol_fingerprint`].
+///
+/// Items are identified by their type names, which Rust only keeps stable for a given compiler.
+/// Peers built from the same code with different compilers may be told that their protocols
+/// don't match.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProtocolFingerprint(pub u64);
+
+impl ProtocolFingerprint {
+    /// Has to be computed once the whole protocol has been registered
+    pub fn of(world: &World, zusammen: &dyn ZusammenPlugin) -> Self {
+        let mut hasher = FingerprintHasher::default();
+        hash_registry(&mut hasher, "channel", world.resource::<ChannelRegistry>());
+        hash_registry(&mut hasher, "message", world.resource::<MessageRegistry>());
+        hash_registry(
+            &mut hasher,
+            "component",
+            world.resource::<ComponentRegistry>(),
+        );
+        zusammen.protocol_fingerprint(&mut hasher);
+        Self(hasher.finish())
+    }
+}
+
+/// Lightyear registry, assigning network ids to the registered types in registration order
+trait ProtocolRegistry {
+    /// Name of the type with the network id `net_id`, `None` past the last one
+    fn item_name(&self, net_id: NetId) -> Option<&str>;
+}
+
+impl ProtocolRegistry for ChannelRegistry {
+    fn item_name(&self, net_id: NetId) -> Option<&str> {
+        self.name(self.get_kind_from_net_id(net_id)?)
+    }
+}
+
+impl ProtocolRegistry for MessageRegistry {
+    fn item_name(&self, net_id: NetId) -> Option<&str> {
+        self.name(self.get_kind_from_net_id(net_id)?)
+    }
+}
+
+impl ProtocolRegistry for ComponentRegistry {
+    fn item_name(&self, net_id: NetId) -> Option<&str> {
+        self.name(self.get_kind_from_net_id(net_id)?)
+    }
+}
+
+fn hash_registry(hasher: &mut dyn Hasher, kind: &str, registry: &impl ProtocolRegistry) {
+    let names = (0..=NetId::MAX).map_while(|net_id| registry.item_name(net_id));
+    for name in names {
+        hasher.write(kind.as_bytes());
+        hasher.write(name.as_bytes());
+        // Separates the names, so that e.g. `ab`, `c` and `a`, `bc` don't hash the same
+        hasher.write_u8(0);
+    }
+}
+
+/// FNV-1a. Unlike the std hashers, its output doesn't depend on the platform or on a random seed.
+struct FingerprintHasher(u64);
+
+impl Default for FingerprintHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for FingerprintHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Sent by the client right after connecting, the server decides whether to admit it.
+///
+/// Peers built from different versions must still be able to decode this, so its layout (and
+/// that of [`JoinResponse`]) should not change.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct JoinRequest {
+    pub game: GameInfo,
+    pub protocol_fingerprint: u64,
+    pub password: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum JoinResponse {
+    Accepted,
+    /// The server disconnects the client shortly after sending this
+    Rejected(ZusammenConnectionError),
+}
+
+/// Why the server refused a client
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ZusammenConnectionError {
+    ServerFull,
+    WrongPassword,
+    AddressNotAllowed,
+    /// The server runs a different game, or an incompatible version of it
+    VersionMismatch {
+        server: GameInfo,
+        client: GameInfo,
+    },
+    /// Same game and compatible versions, but built with a different set of plugins
+    ProtocolMismatch,
+}
+
+impl fmt::Display for ZusammenConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZusammenConnectionError::ServerFull => write!(f, "The server is full"),
+            ZusammenConnectionError::WrongPassword => write!(f, "Wrong password"),
+            ZusammenConnectionError::AddressNotAllowed => {
+                write!(
+                    f,
+                    "The server does not accept connections from this address"
+                )
+            }
+            ZusammenConnectionError::VersionMismatch { server, client } => write!(
+                f,
+                "The server runs {} {}, which is incompatible with {} {}",
+                server.name, server.version, client.name, client.version
+            ),
+            ZusammenConnectionError::ProtocolMismatch => write!(
+                f,
+                "The server was built with a different protocol, check that both use the same plugins"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ZusammenConnectionError {}
+
 pub struct ProtocolPlugin {
     pub zusammen: Arc<dyn ZusammenPlugin + Send + Sync + 'static>,
 }
 
 impl Plugin for ProtocolPlugin {
     fn build(&self, app: &mut App) {
+        // Registered before the user protocol, so that these keep the same ids whatever the
+        // ZusammenPlugin is
+        app.add_channel::<ZusammenChannel>(ChannelSettings {
+            mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+            ..default()
+        });
+        app.register_message::<JoinRequest>(ChannelDirection::ClientToServer);
+        app.register_message::<JoinResponse>(ChannelDirection::ServerToClient);
+
         self.zusammen.add_protocol(app);
+
+        let fingerprint = ProtocolFingerprint::of(app.world(), self.zusammen.as_ref());
+        app.insert_resource(fingerprint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compatible(a: &str, b: &str) -> bool {
+        GameInfo::new("game", a).is_compatible_with(&GameInfo::new("game", b))
+    }
+
+    #[test]
+    fn same_major_version_is_compatible() {
+        assert!(compatible("1.2.3", "1.2.3"));
+        assert!(compatible("1.2.3", "1.9.0"));
+        assert!(!compatible("1.2.3", "2.2.3"));
+    }
+
+    #[test]
+    fn zero_major_versions_need_the_same_minor() {
+        assert!(compatible("0.3.1", "0.3.7"));
+        assert!(!compatible("0.3.1", "0.4.1"));
+        assert!(!compatible("0.3.1", "1.3.1"));
+    }
+
+    #[test]
+    fn pre_release_and_build_metadata_are_ignored() {
+        assert!(compatible("1.2.3-beta.1", "1.0.0"));
+        assert!(compatible("1.2.3+abc", "1.2.3"));
+    }
+
+    #[test]
+    fn other_versions_must_match_exactly() {
+        assert!(compatible("nightly", "nightly"));
+        assert!(!compatible("nightly", "nightly-2"));
+        assert!(!compatible("1.2", "1.2.0"));
+        assert!(!compatible("1.2.3.4", "1.2.3"));
+    }
+
+    #[test]
+    fn different_games_are_incompatible() {
+        let game = GameInfo::new("game", "1.0.0");
+        assert!(!game.is_compatible_with(&GameInfo::new("other", "1.0.0")));
     }
 }
@@ -1,14 +1,338 @@
-use std::sync::Arc;
+use std::{net::IpAddr, sync::Arc, time::Duration};
 
-use bevy::prelude::*;
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use lightyear::{
+    connection::server::NetServer,
+    prelude::{
+        server::{
+            ConnectEvent, ConnectionManager, DisconnectEvent, MessageEvent, ReplicationTarget,
+            ServerConnections,
+        },
+        ClientId, NetworkTarget, ReplicationSet,
+    },
+};
 use zusammen_plugin::ZusammenPlugin;
 
+use crate::{
+    apps::AdmissionParams,
+    protocol::{
+        GameInfo, JoinRequest, JoinResponse, ProtocolFingerprint, ZusammenChannel,
+        ZusammenConnectionError,
+    },
+};
+
+/// Time given to a rejection message to reach the client before disconnecting it
+const REJECTION_GRACE_PERIOD: Duration = Duration::from_secs(1);
+/// Clients that haven't been admitted this long after connecting are disconnected
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sent on the server when a client has been accepted through its [`JoinRequest`]. Games should
+/// spawn players on this rather than on lightyear's connection event, as rejected clients are
+/// also briefly connected.
+#[derive(Event, Clone, Debug)]
+pub struct ClientAdmittedEvent {
+    pub client_id: ClientId,
+}
+
+/// Clients accepted through their [`JoinRequest`]. Entities are only replicated to these, see
+/// [`ServerPlugin`].
+#[derive(Resource, Default)]
+pub struct AdmittedClients(HashSet<ClientId>);
+
+impl AdmittedClients {
+    pub fn contains(&self, client_id: ClientId) -> bool {
+        self.0.contains(&client_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ClientId> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+#[derive(Resource, Default)]
+struct PendingRejections(Vec<(ClientId, Timer)>);
+
+/// Connected clients that haven't sent their [`JoinRequest`] yet, with their [`JOIN_TIMEOUT`]
+#[derive(Resource, Default)]
+struct PendingJoins(HashMap<ClientId, Timer>);
+
+/// Replication target the game gave the entity. The entity's [`ReplicationTarget`] is kept to
+/// the admitted clients among these.
+#[derive(Component)]
+struct RequestedReplicationTarget(NetworkTarget);
+
+/// Admits the clients that connect, see [`AdmissionParams`]. Clients have to send a
+/// [`JoinRequest`] within [`JOIN_TIMEOUT`] of connecting, and entities are only replicated to
+/// the admitted ones.
 pub struct ServerPlugin {
     pub zusammen: Arc<dyn ZusammenPlugin + Send + Sync + 'static>,
+    pub admission: AdmissionParams,
 }
 
 impl Plugin for ServerPlugin {
     fn build(&self, app: &mut App) {
+        app.insert_resource(self.admission.clone())
+            .init_resource::<AdmittedClients>()
+            .init_resource::<PendingRejections>()
+            .init_resource::<PendingJoins>()
+            .add_event::<ClientAdmittedEvent>()
+            .observe(restrict_new_replication_target)
+            .add_systems(
+                Update,
+                (
+                    (
+                        check_connected_clients,
+                        handle_join_requests,
+                        forget_disconnected_clients,
+                    )
+                        .chain(),
+                    disconnect_rejected_clients,
+                    disconnect_unjoined_clients,
+                ),
+            )
+            // Before the entities are sent, so that a target changed by the game this frame
+            // never reaches clients that aren't admitted
+            .add_systems(
+                PostUpdate,
+                restrict_replication_targets.before(ReplicationSet::Send),
+            );
+
         self.zusammen.add_server(app);
     }
 }
+
+/// IP address a client is connected from. In-process clients connected through local channels
+/// get a loopback address.
+pub fn client_ip(connections: &ServerConnections, client_id: ClientId) -> Option<IpAddr> {
+    connections
+        .servers
+        .iter()
+        .find_map(|server| server.client_addr(client_id))
+        .map(|addr| addr.ip())
+}
+
+fn admit(
+    admission: &AdmissionParams,
+    admitted: &AdmittedClients,
+    (game, fingerprint): (&GameInfo, ProtocolFingerprint),
+    request: &JoinRequest,
+) -> Result<(), ZusammenConnectionError> {
+    if !game.is_compatible_with(&request.game) {
+        return Err(ZusammenConnectionError::VersionMismatch {
+            server: game.clone(),
+            client: request.game.clone(),
+        });
+    }
+    if fingerprint.0 != request.protocol_fingerprint {
+        return Err(ZusammenConnectionError::ProtocolMismatch);
+    }
+    if admission.password.is_some() && admission.password != request.password {
+        return Err(ZusammenConnectionError::WrongPassword);
+    }
+    if admission
+        .max_clients
+        .is_some_and(|max_clients| admitted.0.len() >= max_clients)
+    {
+        return Err(ZusammenConnectionError::ServerFull);
+    }
+    Ok(())
+}
+
+/// Tells the client why it's rejected, and disconnects it once the message had time to arrive
+fn reject(
+    client_id: ClientId,
+    ip: Option<IpAddr>,
+    reason: ZusammenConnectionError,
+    connection: &mut ConnectionManager,
+    pending_rejections: &mut PendingRejections,
+) {
+    info!("Rejecting client {client_id:?} ({ip:?}): {reason}");
+    pending_rejections.0.push((
+        client_id,
+        Timer::new(REJECTION_GRACE_PERIOD, TimerMode::Once),
+    ));
+    let response = JoinResponse::Rejected(reason);
+    if let Err(e) = connection.send_message::<ZusammenChannel, _>(client_id, &response) {
+        error!("Could not send join response to client {client_id:?}: {e:?}");
+    }
+}
+
+/// Rejects clients from filtered addresses as soon as they connect, and starts the join timeout
+/// of the others
+fn check_connected_clients(
+    mut connect_events: EventReader<ConnectEvent>,
+    mut connection: ResMut<ConnectionManager>,
+    connections: Res<ServerConnections>,
+    admission: Res<AdmissionParams>,
+    mut pending_joins: ResMut<PendingJoins>,
+    mut pending_rejections: ResMut<PendingRejections>,
+) {
+    for event in connect_events.read() {
+        let client_id = event.client_id;
+        let ip = client_ip(&connections, client_id);
+        if ip.is_some_and(|ip| !admission.address_filter.accepts(ip)) {
+            let reason = ZusammenConnectionError::AddressNotAllowed;
+            reject(
+                client_id,
+                ip,
+                reason,
+                &mut connection,
+                &mut pending_rejections,
+            );
+            continue;
+        }
+        pending_joins
+            .0
+            .insert(client_id, Timer::new(JOIN_TIMEOUT, TimerMode::Once));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_join_requests(
+    mut requests: EventReader<MessageEvent<JoinRequest>>,
+    mut connection: ResMut<ConnectionManager>,
+    connections: Res<ServerConnections>,
+    admission: Res<AdmissionParams>,
+    game: Res<GameInfo>,
+    fingerprint: Res<ProtocolFingerprint>,
+    mut admitted: ResMut<AdmittedClients>,
+    mut pending_joins: ResMut<PendingJoins>,
+    mut pending_rejections: ResMut<PendingRejections>,
+    mut admitted_events: EventWriter<ClientAdmittedEvent>,
+) {
+    for request in requests.read() {
+        let client_id = *request.context();
+        // Repeated requests, and requests of clients that were already rejected (e.g. because
+        // of their address) are ignored
+        if pending_joins.0.remove(&client_id).is_none() {
+            continue;
+        }
+        let ip = client_ip(&connections, client_id);
+
+        match admit(
+            &admission,
+            &admitted,
+            (&game, *fingerprint),
+            request.message(),
+        ) {
+            Ok(()) => {
+                admitted.0.insert(client_id);
+                admitted_events.send(ClientAdmittedEvent { client_id });
+                let response = JoinResponse::Accepted;
+                if let Err(e) = connection.send_message::<ZusammenChannel, _>(client_id, &response)
+                {
+                    error!("Could not send join response to client {client_id:?}: {e:?}");
+                }
+            }
+            Err(reason) => reject(
+                client_id,
+                ip,
+                reason,
+                &mut connection,
+                &mut pending_rejections,
+            ),
+        }
+    }
+}
+
+fn disconnect_unjoined_clients(
+    time: Res<Time<Real>>,
+    mut pending_joins: ResMut<PendingJoins>,
+    mut connections: ResMut<ServerConnections>,
+) {
+    pending_joins.0.retain(|client_id, timer| {
+        if !timer.tick(time.delta()).finished() {
+            return true;
+        }
+        info!("Disconnecting client {client_id:?}, it did not join within {JOIN_TIMEOUT:?}");
+        if let Err(e) = connections.disconnect(*client_id) {
+            warn!("Could not disconnect client {client_id:?}: {e:?}");
+        }
+        false
+    });
+}
+
+fn disconnect_rejected_clients(
+    time: Res<Time<Real>>,
+    mut pending_rejections: ResMut<PendingRejections>,
+    mut connections: ResMut<ServerConnections>,
+) {
+    pending_rejections.0.retain_mut(|(client_id, timer)| {
+        if !timer.tick(time.delta()).finished() {
+            return true;
+        }
+        if let Err(e) = connections.disconnect(*client_id) {
+            warn!("Could not disconnect rejected client {client_id:?}: {e:?}");
+        }
+        false
+    });
+}
+
+fn forget_disconnected_clients(
+    mut disconnections: EventReader<DisconnectEvent>,
+    mut admitted: ResMut<AdmittedClients>,
+    mut pending_joins: ResMut<PendingJoins>,
+    mut pending_rejections: ResMut<PendingRejections>,
+) {
+    for disconnection in disconnections.read() {
+        let client_id = disconnection.client_id;
+        admitted.0.remove(&client_id);
+        pending_joins.0.remove(&client_id);
+        pending_rejections
+            .0
+            .retain(|(rejected, _)| *rejected != client_id);
+    }
+}
+
+/// The clients of `requested` that have been admitted
+fn admitted_target(requested: &NetworkTarget, admitted: &AdmittedClients) -> NetworkTarget {
+    NetworkTarget::Only(
+        admitted
+            .iter()
+            .filter(|client_id| requested.targets(client_id))
+            .collect(),
+    )
+}
+
+/// Restricts the replication of new entities before it starts
+fn restrict_new_replication_target(
+    trigger: Trigger<OnAdd, ReplicationTarget>,
+    mut commands: Commands,
+    mut targets: Query<&mut ReplicationTarget>,
+    admitted: Res<AdmittedClients>,
+) {
+    let entity = trigger.entity();
+    let Ok(mut target) = targets.get_mut(entity) else {
+        return;
+    };
+    let requested = target.target.clone();
+    target.target = admitted_target(&requested, &admitted);
+    commands
+        .entity(entity)
+        .insert(RequestedReplicationTarget(requested));
+}
+
+/// Keeps the replication targets up to date with the admitted clients, and with the targets set
+/// by the game after spawning
+fn restrict_replication_targets(
+    admitted: Res<AdmittedClients>,
+    mut targets: Query<(&mut ReplicationTarget, &mut RequestedReplicationTarget)>,
+) {
+    for (mut target, mut requested) in &mut targets {
+        if !target.is_changed() && !admitted.is_changed() {
+            continue;
+        }
+        let mut restricted = admitted_target(&requested.0, &admitted);
+        if target.is_changed() && target.target != restricted {
+            // Set by the game since it was last restricted
+            requested.0 = target.target.clone();
+            restricted = admitted_target(&requested.0, &admitted);
+        }
+        if target.target != restricted {
+            target.target = restricted;
+        }
+    }
+}
@@ -3,14 +3,16 @@ use std::sync::Arc;
 use bevy::prelude::*;
 use zusammen_plugin::ZusammenPlugin;
 
-use crate::protocol::ProtocolPlugin;
+use crate::protocol::{GameInfo, ProtocolPlugin};
 
 pub struct SharedPlugin {
     pub zusammen: Arc<dyn ZusammenPlugin + Send + Sync + 'static>,
+    pub game: GameInfo,
 }
 
 impl Plugin for SharedPlugin {
     fn build(&self, app: &mut App) {
+        app.insert_resource(self.game.clone());
         app.add_plugins(ProtocolPlugin {
             zusammen: self.zusammen.clone(),
         });
@@ -1,6 +1,6 @@
 use crate::ZusammenPlugin;
 use bevy::prelude::*;
-use std::sync::Arc;
+use std::{hash::Hasher, sync::Arc};
 
 #[derive(Default, Clone)]
 pub struct CombinedPlugins {
@@ -44,4 +44,10 @@ impl ZusammenPlugin for CombinedPlugins {
             plug.add_client(app);
         }
     }
+
+    fn protocol_fingerprint(&self, hasher: &mut dyn Hasher) {
+        for plug in &self.plugins {
+            plug.protocol_fingerprint(hasher);
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use std::hash::Hasher;
+
 use bevy::prelude::*;
 
 pub trait ZusammenPlugin {
@@ -5,4 +7,11 @@ pub trait ZusammenPlugin {
     fn add_shared(&self, app: &mut App);
     fn add_server(&self, app: &mut App);
     fn add_client(&self, app: &mut App);
+
+    /// Feeds whatever else identifies the protocol added in [`ZusammenPlugin::add_protocol`] to
+    /// the hasher, so that peers with different protocols can refuse to talk to each other. The
+    /// channels, messages and components registered with lightyear are always part of the
+    /// fingerprint, this is only needed for settings that change the protocol without changing
+    /// what is registered.
+    fn protocol_fingerprint(&self, _: &mut dyn Hasher) {}
 }
@@ -0,0 +1,255 @@
+//! In-process test harness for [`ZusammenPlugin`]s.
+//!
+//! Builds one headless server app and any number of headless client apps, connected through
+//! crossbeam channels the same way Host mode connects its client. Every app uses a manual time
+//! source that advances by exactly one tick per update, so tests are not affected by how fast the
+//! machine running them is.
+
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use bevy::{
+    ecs::{query::QueryFilter, system::RunSystemOnce},
+    prelude::*,
+    time::TimeUpdateStrategy,
+};
+use lightyear::prelude::{
+    client::{self, ClientCommands},
+    server::ServerCommands,
+};
+use zusammen_app::{
+    headless_client_app, headless_server_app, AdmissionParams, ClientParams, ClientTransportParams,
+    ClientZusammenAppManager, GameInfo, LinkConditionerParams, ServerParams, SharedParams,
+    ZusammenAppConfig, ZusammenAppMode,
+};
+use zusammen_plugin::ZusammenPlugin;
+
+#[derive(Clone, Debug)]
+pub struct ZusammenTestConfig {
+    pub n_clients: usize,
+    pub tick_duration: Duration,
+    pub maximum_input_delay_ticks: u16,
+    pub correction_ticks_factor: f32,
+    /// Number of ticks to wait for every client to connect and be admitted before giving up
+    pub connection_timeout_ticks: u32,
+    pub client_link_conditioner: Option<LinkConditionerParams>,
+    pub server_link_conditioner: Option<LinkConditionerParams>,
+    /// Clients join with the admission password, if any
+    pub admission: AdmissionParams,
+    pub game: GameInfo,
+}
+
+impl Default for ZusammenTestConfig {
+    fn default() -> Self {
+        Self {
+            n_clients: 1,
+            tick_duration: Duration::from_secs_f64(1. / 64.),
+            maximum_input_delay_ticks: 0,
+            correction_ticks_factor: 1.,
+            connection_timeout_ticks: 500,
+            client_link_conditioner: None,
+            server_link_conditioner: None,
+            admission: AdmissionParams::default(),
+            game: GameInfo::new("zusammen_test", "0.1.0"),
+        }
+    }
+}
+
+pub struct ZusammenTest {
+    server: App,
+    clients: Vec<App>,
+}
+
+impl ZusammenTest {
+    /// Builds the server and client apps and waits until every client is connected and admitted
+    /// by the server.
+    ///
+    /// Panics if the clients fail to join within `connection_timeout_ticks`.
+    pub fn new<P: ZusammenPlugin + Send + Sync + 'static>(
+        plugin: P,
+        config: ZusammenTestConfig,
+    ) -> Self {
+        let plugin: Arc<dyn ZusammenPlugin + Send + Sync + 'static> = Arc::new(plugin);
+        let shared_params = SharedParams {
+            tick_duration: config.tick_duration,
+            game: config.game.clone(),
+        };
+
+        let mut local_channels = vec![];
+        let mut clients = vec![];
+        for _ in 0..config.n_clients {
+            let (from_server_send, from_server_recv) = crossbeam_channel::unbounded();
+            let (to_server_send, to_server_recv) = crossbeam_channel::unbounded();
+            local_channels.push((to_server_recv, from_server_send));
+
+            let client_params = ClientParams {
+                transport: ClientTransportParams::LocalChannel {
+                    recv: from_server_recv,
+                    send: to_server_send,
+                },
+                maximum_input_delay_ticks: config.maximum_input_delay_ticks,
+                correction_ticks_factor: config.correction_ticks_factor,
+                link_conditioner: config.client_link_conditioner,
+                password: config.admission.password.clone(),
+            };
+            let app_config = ZusammenAppConfig {
+                plugin: plugin.clone(),
+                mode: ZusammenAppMode::Host { port: 0 },
+                client_link_conditioner: config.client_link_conditioner,
+                server_link_conditioner: config.server_link_conditioner,
+                admission: config.admission.clone(),
+                password: config.admission.password.clone(),
+                game: config.game.clone(),
+            };
+
+            let mut client = headless_client_app(&client_params, &shared_params, app_config);
+            client.insert_resource(TimeUpdateStrategy::ManualDuration(config.tick_duration));
+            clients.push(client);
+        }
+
+        // Port 0 lets the OS pick a free port, so that tests can run in parallel
+        let server_params = ServerParams {
+            port: 0,
+            local_channels,
+            link_conditioner: config.server_link_conditioner,
+            admission: config.admission.clone(),
+        };
+        let mut server = headless_server_app(&server_params, &shared_params, plugin);
+        server.insert_resource(TimeUpdateStrategy::ManualDuration(config.tick_duration));
+
+        let mut test = Self { server, clients };
+        test.connect(config.connection_timeout_ticks);
+        test
+    }
+
+    fn connect(&mut self, timeout_ticks: u32) {
+        self.server
+            .world_mut()
+            .run_system_once(|mut commands: Commands| commands.start_server());
+        self.server.update();
+
+        for client in &mut self.clients {
+            client
+                .world_mut()
+                .run_system_once(|mut commands: Commands| commands.connect_client());
+        }
+
+        let joined = self.step_until(timeout_ticks, |test| {
+            (0..test.n_clients()).all(|i| test.is_client_connected(i) && test.is_client_admitted(i))
+        });
+        assert!(joined, "Clients did not join within {timeout_ticks} ticks");
+    }
+
+    /// Advances the server and then every client by `n_ticks` ticks
+    pub fn step(&mut self, n_ticks: u32) {
+        for _ in 0..n_ticks {
+            self.server.update();
+            for client in &mut self.clients {
+                client.update();
+            }
+        }
+    }
+
+    /// Advances one tick at a time until `condition` holds, for at most `max_ticks` ticks.
+    /// Returns whether it held.
+    pub fn step_until(
+        &mut self,
+        max_ticks: u32,
+        mut condition: impl FnMut(&mut Self) -> bool,
+    ) -> bool {
+        for _ in 0..max_ticks {
+            if condition(self) {
+                return true;
+            }
+            self.step(1);
+        }
+        condition(self)
+    }
+
+    pub fn server(&mut self) -> &mut App {
+        &mut self.server
+    }
+
+    pub fn client(&mut self, i: usize) -> &mut App {
+        &mut self.clients[i]
+    }
+
+    pub fn n_clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn is_client_connected(&self, i: usize) -> bool {
+        self.clients[i]
+            .world()
+            .get_resource::<State<client::NetworkingState>>()
+            .is_some_and(|state| *state.get() == client::NetworkingState::Connected)
+    }
+
+    /// Whether the server accepted the join request of client `i`
+    pub fn is_client_admitted(&mut self, i: usize) -> bool {
+        self.clients[i]
+            .world_mut()
+            .run_system_once(|manager: ClientZusammenAppManager| manager.is_admitted())
+    }
+
+    /// Number of entities matching the filter `F` in the server world
+    pub fn server_count<F: QueryFilter>(&mut self) -> usize {
+        count::<F>(&mut self.server)
+    }
+
+    /// Number of entities matching the filter `F` in the world of client `i`
+    pub fn client_count<F: QueryFilter>(&mut self, i: usize) -> usize {
+        count::<F>(&mut self.clients[i])
+    }
+
+    /// Entities in the world of client `i` with a `C` component equal to `value`
+    pub fn client_entities_with<C: Component + PartialEq>(
+        &mut self,
+        i: usize,
+        value: &C,
+    ) -> Vec<Entity> {
+        let world = self.clients[i].world_mut();
+        world
+            .query::<(Entity, &C)>()
+            .iter(world)
+            .filter(|(_, c)| *c == value)
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    /// Asserts that every client has at least one entity whose `C` component matches the one of
+    /// `server_entity` in the server world.
+    pub fn assert_replicated_to_all_clients<C: Component + PartialEq + Clone + Debug>(
+        &mut self,
+        server_entity: Entity,
+    ) {
+        let Some(value) = self.server.world().get::<C>(server_entity).cloned() else {
+            panic!(
+                "Server entity {server_entity:?} has no {}",
+                std::any::type_name::<C>()
+            );
+        };
+
+        for i in 0..self.clients.len() {
+            assert!(
+                !self.client_entities_with(i, &value).is_empty(),
+                "{value:?} of server entity {server_entity:?} was not replicated to client {i}",
+            );
+        }
+    }
+
+    /// Asserts that every client has exactly `n` entities matching the filter `F`
+    pub fn assert_count_on_all_clients<F: QueryFilter>(&mut self, n: usize) {
+        for i in 0..self.clients.len() {
+            let count = self.client_count::<F>(i);
+            assert_eq!(
+                count, n,
+                "Client {i} has {count} matching entities, expected {n}"
+            );
+        }
+    }
+}
+
+fn count<F: QueryFilter>(app: &mut App) -> usize {
+    let world = app.world_mut();
+    world.query_filtered::<Entity, F>().iter(world).count()
+}
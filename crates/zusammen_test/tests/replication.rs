@@ -0,0 +1,95 @@
+use bevy::prelude::*;
+use lightyear::prelude::{client, server, ChannelDirection, NetworkTarget};
+use serde::{Deserialize, Serialize};
+use zusammen_plugin::ZusammenPlugin;
+use zusammen_test::{ZusammenTest, ZusammenTestConfig};
+
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Score(u32);
+
+struct ScorePlugin;
+
+impl ZusammenPlugin for ScorePlugin {
+    fn add_protocol(&self, app: &mut App) {
+        app.register_component::<Score>(ChannelDirection::ServerToClient)
+            .add_prediction(client::ComponentSyncMode::Full);
+    }
+
+    fn add_shared(&self, _: &mut App) {}
+
+    fn add_server(&self, _: &mut App) {}
+
+    fn add_client(&self, _: &mut App) {}
+}
+
+const N_CLIENTS: usize = 3;
+/// Ticks to wait for an entity to reach every client before failing
+const REPLICATION_TIMEOUT_TICKS: u32 = 500;
+
+fn connected_test() -> ZusammenTest {
+    let mut test = ZusammenTest::new(
+        ScorePlugin,
+        ZusammenTestConfig {
+            n_clients: N_CLIENTS,
+            ..default()
+        },
+    );
+    assert_eq!(test.n_clients(), N_CLIENTS);
+    for i in 0..N_CLIENTS {
+        assert!(test.is_client_connected(i), "Client {i} is not connected");
+        assert!(test.is_client_admitted(i), "Client {i} is not admitted");
+    }
+    test
+}
+
+#[test]
+fn entity_is_replicated_to_all_clients() {
+    let mut test = connected_test();
+    let entity = test
+        .server()
+        .world_mut()
+        .spawn((Score(7), server::Replicate::default()))
+        .id();
+    let replicated = test.step_until(REPLICATION_TIMEOUT_TICKS, |test| {
+        (0..N_CLIENTS).all(|i| test.client_count::<With<Score>>(i) > 0)
+    });
+    assert!(
+        replicated,
+        "Score was not replicated within {REPLICATION_TIMEOUT_TICKS} ticks"
+    );
+
+    test.assert_replicated_to_all_clients::<Score>(entity);
+    assert_eq!(test.server_count::<With<Score>>(), 1);
+    test.assert_count_on_all_clients::<(With<Score>, Without<client::Predicted>)>(1);
+}
+
+#[test]
+fn predicted_entity_is_spawned_on_all_clients() {
+    let mut test = connected_test();
+    test.server().world_mut().spawn((
+        Score(3),
+        server::Replicate {
+            sync: server::SyncTarget {
+                prediction: NetworkTarget::All,
+                ..default()
+            },
+            ..default()
+        },
+    ));
+    let predicted = test.step_until(REPLICATION_TIMEOUT_TICKS, |test| {
+        (0..N_CLIENTS).all(|i| test.client_count::<(With<Score>, With<client::Predicted>)>(i) > 0)
+    });
+    assert!(
+        predicted,
+        "Score was not predicted within {REPLICATION_TIMEOUT_TICKS} ticks"
+    );
+
+    // The confirmed entity, and the predicted one spawned from it
+    test.assert_count_on_all_clients::<(With<Score>, With<client::Confirmed>)>(1);
+    test.assert_count_on_all_clients::<(With<Score>, With<client::Predicted>)>(1);
+    for i in 0..N_CLIENTS {
+        let world = test.client(i).world_mut();
+        let mut query = world.query_filtered::<&Score, With<client::Predicted>>();
+        assert_eq!(query.single(world), &Score(3));
+    }
+}